@@ -9,5 +9,6 @@ pub use mlua;
 pub use ratatui::crossterm::event::*;
 pub use ratatui::crossterm::*;
 pub use ratatui::prelude::*;
+pub use ratatui::style;
 pub use ratatui::widgets::*;
 pub use ratatui::*;
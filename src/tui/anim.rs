@@ -0,0 +1,447 @@
+use crate::tui::timer::lua_secs;
+use crate::{LuaError, LuaResult, LuaTable, LuaValue};
+use mlua::{FromLua, Lua};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::rc::Rc;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Easing {
+   #[default]
+   Linear,
+   InQuad,
+   OutQuad,
+   InOutQuad,
+   InCubic,
+   OutCubic,
+   InOutCubic,
+   InSine,
+   OutSine,
+   InOutSine,
+   InExpo,
+   OutExpo,
+   OutBack,
+   OutElastic,
+   OutBounce,
+}
+
+impl Easing {
+   pub fn from_name(name: &str) -> Option<Self> {
+      match name.to_lowercase().replace('-', "_").as_str() {
+         "linear" => Some(Easing::Linear),
+         "in_quad" => Some(Easing::InQuad),
+         "out_quad" => Some(Easing::OutQuad),
+         "in_out_quad" => Some(Easing::InOutQuad),
+         "in_cubic" => Some(Easing::InCubic),
+         "out_cubic" => Some(Easing::OutCubic),
+         "in_out_cubic" => Some(Easing::InOutCubic),
+         "in_sine" => Some(Easing::InSine),
+         "out_sine" => Some(Easing::OutSine),
+         "in_out_sine" => Some(Easing::InOutSine),
+         "in_expo" => Some(Easing::InExpo),
+         "out_expo" => Some(Easing::OutExpo),
+         "out_back" => Some(Easing::OutBack),
+         "out_elastic" => Some(Easing::OutElastic),
+         "out_bounce" => Some(Easing::OutBounce),
+         _ => None,
+      }
+   }
+
+   // maps progress in 0..1 to eased progress, back and elastic overshoot 1 briefly
+   pub fn apply(&self, t: f32) -> f32 {
+      let t = t.clamp(0.0, 1.0);
+      match self {
+         Easing::Linear => t,
+         Easing::InQuad => t * t,
+         Easing::OutQuad => 1.0 - (1.0 - t).powi(2),
+         Easing::InOutQuad => match t < 0.5 {
+            true => 2.0 * t * t,
+            false => 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0,
+         },
+         Easing::InCubic => t.powi(3),
+         Easing::OutCubic => 1.0 - (1.0 - t).powi(3),
+         Easing::InOutCubic => match t < 0.5 {
+            true => 4.0 * t.powi(3),
+            false => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+         },
+         Easing::InSine => 1.0 - (t * PI / 2.0).cos(),
+         Easing::OutSine => (t * PI / 2.0).sin(),
+         Easing::InOutSine => -((PI * t).cos() - 1.0) / 2.0,
+         Easing::InExpo if t == 0.0 => 0.0,
+         Easing::InExpo => 2f32.powf(10.0 * t - 10.0),
+         Easing::OutExpo if t == 1.0 => 1.0,
+         Easing::OutExpo => 1.0 - 2f32.powf(-10.0 * t),
+         Easing::OutBack => {
+            let c1 = 1.70158;
+            let c3 = c1 + 1.0;
+            1.0 + c3 * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2)
+         }
+         Easing::OutElastic if t == 0.0 || t == 1.0 => t,
+         Easing::OutElastic => {
+            let c4 = (2.0 * PI) / 3.0;
+            2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * c4).sin() + 1.0
+         }
+         Easing::OutBounce => {
+            let (n1, d1) = (7.5625, 2.75);
+            if t < 1.0 / d1 {
+               n1 * t * t
+            } else if t < 2.0 / d1 {
+               let t = t - 1.5 / d1;
+               n1 * t * t + 0.75
+            } else if t < 2.5 / d1 {
+               let t = t - 2.25 / d1;
+               n1 * t * t + 0.9375
+            } else {
+               let t = t - 2.625 / d1;
+               n1 * t * t + 0.984375
+            }
+         }
+      }
+   }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Repeat {
+   #[default]
+   Once,
+   Loop,
+   // plays forward then backward, forever
+   PingPong,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tween {
+   pub from: f32,
+   pub to: f32,
+   pub duration: Duration,
+   pub delay: Duration,
+   pub easing: Easing,
+   pub repeat: Repeat,
+}
+
+impl Tween {
+   pub fn new(from: f32, to: f32, duration: Duration) -> Self {
+      Self {
+         from,
+         to,
+         duration,
+         delay: Duration::ZERO,
+         easing: Easing::default(),
+         repeat: Repeat::default(),
+      }
+   }
+   pub fn easing(mut self, easing: Easing) -> Self {
+      self.easing = easing;
+      self
+   }
+   pub fn delay(mut self, delay: Duration) -> Self {
+      self.delay = delay;
+      self
+   }
+   pub fn repeat(mut self, repeat: Repeat) -> Self {
+      self.repeat = repeat;
+      self
+   }
+
+   // linear progress in 0..1 after `elapsed` since the tween started
+   pub fn progress(&self, elapsed: Duration) -> f32 {
+      let running = elapsed.saturating_sub(self.delay).as_secs_f32();
+      let length = self.duration.as_secs_f32();
+      if length <= 0.0 {
+         return 1.0;
+      }
+      let cycles = running / length;
+      match self.repeat {
+         Repeat::Once => cycles.min(1.0),
+         Repeat::Loop => cycles.fract(),
+         Repeat::PingPong => match cycles as u64 % 2 {
+            0 => cycles.fract(),
+            _ => 1.0 - cycles.fract(),
+         },
+      }
+   }
+   pub fn value(&self, elapsed: Duration) -> f32 {
+      let t = self.easing.apply(self.progress(elapsed));
+      self.from + (self.to - self.from) * t
+   }
+   pub fn is_done(&self, elapsed: Duration) -> bool {
+      self.repeat == Repeat::Once && elapsed >= self.delay + self.duration
+   }
+}
+
+// each frame's easing shapes the way in from the previous frame
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Keyframes {
+   frames: Vec<(Duration, f32, Easing)>,
+   looping: bool,
+}
+
+impl Keyframes {
+   pub fn new() -> Self {
+      Self::default()
+   }
+   pub fn frame(mut self, at: Duration, value: f32, easing: Easing) -> Self {
+      let i = self.frames.partition_point(|(t, _, _)| *t <= at);
+      self.frames.insert(i, (at, value, easing));
+      self
+   }
+   pub fn looping(mut self, looping: bool) -> Self {
+      self.looping = looping;
+      self
+   }
+   pub fn duration(&self) -> Duration {
+      self.frames.last().map_or(Duration::ZERO, |(t, _, _)| *t)
+   }
+
+   pub fn value(&self, elapsed: Duration) -> f32 {
+      let length = self.duration();
+      let elapsed = match self.looping && !length.is_zero() {
+         true => Duration::from_secs_f32(elapsed.as_secs_f32() % length.as_secs_f32()),
+         false => elapsed,
+      };
+      let next = self.frames.partition_point(|(t, _, _)| *t <= elapsed);
+      let prev = next.checked_sub(1).map(|i| self.frames[i]);
+      match (prev, self.frames.get(next).copied()) {
+         (None, Some((_, v, _))) | (Some((_, v, _)), None) => v,
+         (Some((t0, v0, _)), Some((t1, v1, easing))) => {
+            let span = (t1 - t0).as_secs_f32();
+            let t = match span > 0.0 {
+               true => (elapsed - t0).as_secs_f32() / span,
+               false => 1.0,
+            };
+            v0 + (v1 - v0) * easing.apply(t)
+         }
+         (None, None) => 0.0,
+      }
+   }
+   pub fn is_done(&self, elapsed: Duration) -> bool {
+      !self.looping && elapsed >= self.duration()
+   }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Anim {
+   Tween(Tween),
+   Keyframes(Keyframes),
+}
+
+impl Anim {
+   pub fn value(&self, elapsed: Duration) -> f32 {
+      match self {
+         Anim::Tween(t) => t.value(elapsed),
+         Anim::Keyframes(k) => k.value(elapsed),
+      }
+   }
+   pub fn is_done(&self, elapsed: Duration) -> bool {
+      match self {
+         Anim::Tween(t) => t.is_done(elapsed),
+         Anim::Keyframes(k) => k.is_done(elapsed),
+      }
+   }
+}
+
+#[derive(Debug, Default)]
+struct AnimatorInner {
+   now: Duration,
+   anims: HashMap<String, (Duration, Anim)>,
+}
+
+// named animations timed from runtime start, a cheap handle shared with lua.
+// finished animations hold their last value until removed or restarted
+#[derive(Debug, Clone, Default)]
+pub struct Animator {
+   inner: Rc<RefCell<AnimatorInner>>,
+}
+
+impl Animator {
+   pub(crate) fn new() -> Self {
+      Self::default()
+   }
+
+   pub fn now(&self) -> Duration {
+      self.inner.borrow().now
+   }
+   pub(crate) fn advance(&self, now: Duration) {
+      self.inner.borrow_mut().now = now;
+   }
+
+   pub fn play(&self, name: &str, anim: Anim) {
+      let mut inner = self.inner.borrow_mut();
+      let now = inner.now;
+      inner.anims.insert(name.to_string(), (now, anim));
+   }
+   pub fn tween(&self, name: &str, tween: Tween) {
+      self.play(name, Anim::Tween(tween));
+   }
+   pub fn keyframes(&self, name: &str, keyframes: Keyframes) {
+      self.play(name, Anim::Keyframes(keyframes));
+   }
+
+   pub fn value(&self, name: &str) -> Option<f32> {
+      let inner = self.inner.borrow();
+      let (start, anim) = inner.anims.get(name)?;
+      Some(anim.value(inner.now.saturating_sub(*start)))
+   }
+   pub fn value_or(&self, name: &str, default: f32) -> f32 {
+      self.value(name).unwrap_or(default)
+   }
+   pub fn is_playing(&self, name: &str) -> bool {
+      let inner = self.inner.borrow();
+      match inner.anims.get(name) {
+         Some((start, anim)) => !anim.is_done(inner.now.saturating_sub(*start)),
+         None => false,
+      }
+   }
+   pub fn is_done(&self, name: &str) -> bool {
+      let inner = self.inner.borrow();
+      match inner.anims.get(name) {
+         Some((start, anim)) => anim.is_done(inner.now.saturating_sub(*start)),
+         None => true,
+      }
+   }
+   pub fn remove(&self, name: &str) -> bool {
+      self.inner.borrow_mut().anims.remove(name).is_some()
+   }
+   pub fn clear(&self) {
+      self.inner.borrow_mut().anims.clear();
+   }
+   pub fn names(&self) -> Vec<String> {
+      self.inner.borrow().anims.keys().cloned().collect()
+   }
+}
+
+fn easing_opt(name: Option<String>) -> LuaResult<Easing> {
+   match name {
+      None => Ok(Easing::default()),
+      Some(name) => Easing::from_name(&name)
+         .ok_or_else(|| LuaError::runtime(format!("unknown easing {name:?}"))),
+   }
+}
+
+// `{ from = 0, to = 1, duration = 0.5, easing = "out_cubic", delay = 0, repeat = "loop" }`
+impl FromLua for Tween {
+   fn from_lua(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+      let table = LuaTable::from_lua(value, lua)?;
+      let repeat = match table.get::<Option<String>>("repeat")?.as_deref() {
+         None | Some("once") => Repeat::Once,
+         Some("loop") => Repeat::Loop,
+         Some("ping_pong") | Some("pingpong") => Repeat::PingPong,
+         Some(other) => return Err(LuaError::runtime(format!("unknown repeat {other:?}"))),
+      };
+      Ok(Tween {
+         from: table.get::<Option<f32>>("from")?.unwrap_or(0.0),
+         to: table.get::<Option<f32>>("to")?.unwrap_or(1.0),
+         duration: lua_secs(table.get::<f64>("duration")?)?,
+         delay: lua_secs(table.get::<Option<f64>>("delay")?.unwrap_or(0.0))?,
+         easing: easing_opt(table.get("easing")?)?,
+         repeat,
+      })
+   }
+}
+
+// `{ { 0, 0 }, { 0.3, 1, "out_quad" }, { 1, 0 }, loop = true }`, times in seconds
+impl FromLua for Keyframes {
+   fn from_lua(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+      let table = LuaTable::from_lua(value, lua)?;
+      let looping = table.get::<Option<bool>>("loop")?.unwrap_or(false);
+      let mut keyframes = Keyframes::new().looping(looping);
+      for frame in table.sequence_values::<LuaTable>() {
+         let frame = frame?;
+         let at: f64 = frame.get(1)?;
+         let value: f32 = frame.get(2)?;
+         keyframes = keyframes.frame(lua_secs(at)?, value, easing_opt(frame.get(3)?)?);
+      }
+      Ok(keyframes)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn ms(ms: u64) -> Duration {
+      Duration::from_millis(ms)
+   }
+   fn close(a: f32, b: f32) -> bool {
+      (a - b).abs() < 1e-4
+   }
+
+   #[test]
+   fn easings_start_at_0_and_end_at_1() {
+      let names = [
+         "linear", "in_quad", "out_quad", "in_out_quad", "in_cubic", "out_cubic",
+         "in_out_cubic", "in_sine", "out_sine", "in_out_sine", "in_expo", "out_expo",
+         "out_back", "out_elastic", "out_bounce",
+      ];
+      for name in names {
+         let easing = Easing::from_name(name).unwrap();
+         assert!(close(easing.apply(0.0), 0.0), "{name}");
+         assert!(close(easing.apply(1.0), 1.0), "{name}");
+         assert!(close(easing.apply(2.0), 1.0), "{name} clamps");
+      }
+      assert_eq!(Easing::from_name("Out-Cubic"), Some(Easing::OutCubic));
+      assert_eq!(Easing::from_name("nope"), None);
+   }
+
+   #[test]
+   fn tween_waits_for_its_delay() {
+      let tween = Tween::new(10.0, 20.0, ms(100)).delay(ms(50));
+      assert!(close(tween.value(ms(0)), 10.0));
+      assert!(close(tween.value(ms(100)), 15.0));
+      assert!(!tween.is_done(ms(149)));
+      assert!(tween.is_done(ms(150)));
+      assert!(close(tween.value(ms(500)), 20.0));
+   }
+
+   #[test]
+   fn tween_repeats() {
+      let looped = Tween::new(0.0, 1.0, ms(100)).repeat(Repeat::Loop);
+      assert!(close(looped.value(ms(125)), 0.25));
+      assert!(!looped.is_done(ms(1000)));
+      let ping_pong = Tween::new(0.0, 1.0, ms(100)).repeat(Repeat::PingPong);
+      assert!(close(ping_pong.value(ms(125)), 0.75));
+      assert!(close(Tween::new(0.0, 1.0, ms(0)).value(ms(0)), 1.0));
+   }
+
+   #[test]
+   fn keyframes_interpolate_between_frames() {
+      let keys = Keyframes::new()
+         .frame(ms(100), 10.0, Easing::Linear)
+         .frame(ms(0), 0.0, Easing::Linear)
+         .frame(ms(200), 0.0, Easing::Linear);
+      assert_eq!(keys.duration(), ms(200));
+      assert!(close(keys.value(ms(50)), 5.0));
+      assert!(close(keys.value(ms(150)), 5.0));
+      assert!(close(keys.value(ms(300)), 0.0));
+      assert!(keys.is_done(ms(200)));
+      let looping = keys.looping(true);
+      assert!(close(looping.value(ms(250)), 5.0));
+      assert!(!looping.is_done(ms(1000)));
+   }
+
+   #[test]
+   fn animator_times_from_when_an_anim_started() {
+      let anim = Animator::new();
+      anim.advance(ms(1000));
+      anim.tween("x", Tween::new(0.0, 1.0, ms(100)));
+      anim.advance(ms(1050));
+      assert!(close(anim.value_or("x", -1.0), 0.5));
+      assert!(anim.is_playing("x"));
+      anim.advance(ms(1100));
+      assert!(anim.is_done("x"));
+      assert!(anim.remove("x"));
+      assert_eq!(anim.value("x"), None);
+   }
+
+   #[test]
+   fn lua_durations_are_checked() {
+      let lua = Lua::new();
+      let tween: Tween = lua.load("return { from = 0, to = 1, duration = 0.5 }").eval().unwrap();
+      assert_eq!(tween.duration, ms(500));
+      assert!(lua.load("return { to = 1, duration = -1 }").eval::<Tween>().is_err());
+      assert!(lua.load("return { to = 1, duration = 0/0 }").eval::<Tween>().is_err());
+      assert!(lua.load("return { { math.huge, 1 } }").eval::<Keyframes>().is_err());
+   }
+}
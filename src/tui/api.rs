@@ -0,0 +1,213 @@
+use crate::tui::timer::lua_secs;
+use crate::{
+   markup, Animator, Keyframes, LogSink, LogSource, LuaColor, LuaError, LuaFunction, LuaResult,
+   LuaStyle, LuaTable, LuaValue, MsgType, Store, Theme, Timers, Tween,
+};
+use mlua::{Lua, LuaSerdeExt};
+use ratatui::text::Text;
+
+pub(crate) const API_GLOBAL: &str = "tui";
+
+pub(crate) struct LuaApi {
+   pub(crate) store: Store,
+   pub(crate) logs: LogSink,
+   pub(crate) anim: Animator,
+   pub(crate) timers: Timers,
+}
+
+impl LuaApi {
+   pub(crate) fn install(&self, lua: &Lua) -> LuaResult<()> {
+      let api = lua.create_table()?;
+      api.set("store", self.store_table(lua)?)?;
+      api.set("anim", self.anim_table(lua)?)?;
+
+      let logs = self.logs.clone();
+      let log = lua.create_function(move |_, (msg, level): (String, Option<String>)| {
+         let typ = level
+            .as_deref()
+            .and_then(MsgType::from_name)
+            .unwrap_or(MsgType::Info);
+         logs.push(&msg, typ, LogSource::Lua);
+         Ok(())
+      })?;
+      api.set("log", log)?;
+
+      let timers = self.timers.clone();
+      let after = lua.create_function(move |lua, (secs, f): (f64, LuaFunction)| {
+         timers.after_lua(lua, lua_secs(secs)?, f)
+      })?;
+      api.set("after", after)?;
+
+      let timers = self.timers.clone();
+      let every = lua.create_function(move |lua, (secs, f): (f64, LuaFunction)| {
+         timers.every_lua(lua, lua_secs(secs)?, f)
+      })?;
+      api.set("every", every)?;
+
+      let timers = self.timers.clone();
+      let cancel = lua.create_function(move |_, id: String| Ok(timers.cancel(&id)))?;
+      api.set("cancel", cancel)?;
+
+      let theme = lua.create_function(|lua, ()| theme_table(lua, lua_theme(lua)))?;
+      api.set("theme", theme)?;
+
+      let markup = lua.create_function(|lua, src: String| {
+         let text = markup(&src, &lua_theme(lua)).map_err(|e| LuaError::runtime(e.to_string()))?;
+         text_table(lua, text)
+      })?;
+      api.set("markup", markup)?;
+      lua.globals().set(API_GLOBAL, api)
+   }
+
+   fn store_table(&self, lua: &Lua) -> LuaResult<LuaTable> {
+      let table = lua.create_table()?;
+
+      let store = self.store.clone();
+      let get = lua.create_function(move |lua, key: String| match store.get_value(&key) {
+         Some(v) => lua.to_value(&v),
+         None => Ok(LuaValue::Nil),
+      })?;
+      table.set("get", get)?;
+
+      let store = self.store.clone();
+      let set = lua.create_function(move |lua, (key, value): (String, LuaValue)| {
+         if value.is_nil() {
+            store.remove(&key);
+         } else {
+            store.set_value(&key, lua.from_value(value)?);
+         }
+         Ok(())
+      })?;
+      table.set("set", set)?;
+
+      let store = self.store.clone();
+      let keys = lua.create_function(move |_, ()| Ok(store.keys()))?;
+      table.set("keys", keys)?;
+      Ok(table)
+   }
+
+   fn anim_table(&self, lua: &Lua) -> LuaResult<LuaTable> {
+      let table = lua.create_table()?;
+
+      let anim = self.anim.clone();
+      let tween = lua.create_function(move |_, (name, tween): (String, Tween)| {
+         anim.tween(&name, tween);
+         Ok(())
+      })?;
+      table.set("tween", tween)?;
+
+      let anim = self.anim.clone();
+      let keyframes = lua.create_function(move |_, (name, keys): (String, Keyframes)| {
+         anim.keyframes(&name, keys);
+         Ok(())
+      })?;
+      table.set("keyframes", keyframes)?;
+
+      let anim = self.anim.clone();
+      let value = lua.create_function(move |_, name: String| Ok(anim.value(&name)))?;
+      table.set("value", value)?;
+
+      let anim = self.anim.clone();
+      let done = lua.create_function(move |_, name: String| Ok(anim.is_done(&name)))?;
+      table.set("done", done)?;
+
+      let anim = self.anim.clone();
+      let remove = lua.create_function(move |_, name: String| Ok(anim.remove(&name)))?;
+      table.set("remove", remove)?;
+      Ok(table)
+   }
+}
+
+// the tui keeps the state's app data in sync with its theme
+fn lua_theme(lua: &Lua) -> Theme {
+   lua.app_data_ref::<Theme>().map(|t| t.clone()).unwrap_or_default()
+}
+
+// `tui.theme().style("accent")`, a snapshot of the theme at the time of the call
+fn theme_table(lua: &Lua, theme: Theme) -> LuaResult<LuaTable> {
+   let table = lua.create_table()?;
+   table.set("name", theme.name())?;
+
+   let t = theme.clone();
+   let color = lua.create_function(move |_, slot: String| Ok(t.get(&slot).map(LuaColor)))?;
+   table.set("color", color)?;
+
+   let t = theme.clone();
+   let style = lua.create_function(move |_, slot: String| Ok(LuaStyle(t.style(&slot))))?;
+   table.set("style", style)?;
+
+   let bold = lua.create_function(move |_, slot: String| Ok(LuaStyle(theme.bold(&slot))))?;
+   table.set("bold", bold)?;
+   Ok(table)
+}
+
+// lines of `{ text = "..", style = { fg = .. } }` spans
+fn text_table(lua: &Lua, text: Text) -> LuaResult<LuaTable> {
+   let lines = lua.create_table()?;
+   for line in text.lines {
+      let spans = lua.create_table()?;
+      for span in line.spans {
+         let table = lua.create_table()?;
+         table.set("text", span.content.as_ref())?;
+         table.set("style", LuaStyle(span.style))?;
+         spans.push(table)?;
+      }
+      lines.push(spans)?;
+   }
+   Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn lua() -> Lua {
+      let lua = Lua::new();
+      let api = LuaApi {
+         store: Store::default(),
+         logs: LogSink::default(),
+         anim: Animator::new(),
+         timers: Timers::new(),
+      };
+      api.install(&lua).unwrap();
+      lua
+   }
+
+   #[test]
+   fn theme_is_read_from_app_data() {
+      let lua = lua();
+      let name: String = lua.load("return tui.theme().name").eval().unwrap();
+      assert_eq!(name, "dark");
+      lua.set_app_data(Theme::preset("nord").unwrap());
+      let fg: String = lua.load("return tui.theme().style('accent').fg").eval().unwrap();
+      assert_eq!(fg, "#b48ead");
+      let bold: bool = lua.load("return tui.theme().bold('fg').bold").eval().unwrap();
+      assert!(bold);
+   }
+
+   #[test]
+   fn markup_becomes_lines_of_spans() {
+      let lua = lua();
+      let src = "return tui.markup('a[b accent]b[/]\\nc')";
+      let lines: LuaTable = lua.load(src).eval().unwrap();
+      assert_eq!(lines.len().unwrap(), 2);
+      let first: LuaTable = lines.get(1).unwrap();
+      let span: LuaTable = first.get(2).unwrap();
+      assert_eq!(span.get::<String>("text").unwrap(), "b");
+      let style: LuaTable = span.get("style").unwrap();
+      assert!(style.get::<bool>("bold").unwrap());
+      assert_eq!(style.get::<String>("fg").unwrap(), "#c678dd");
+      assert!(lua.load("tui.markup('[nope]x')").exec().is_err());
+   }
+
+   #[test]
+   fn store_round_trips_lua_values() {
+      let lua = lua();
+      lua.load("tui.store.set('n', { a = 1 })").exec().unwrap();
+      let a: i64 = lua.load("return tui.store.get('n').a").eval().unwrap();
+      assert_eq!(a, 1);
+      lua.load("tui.store.set('n', nil)").exec().unwrap();
+      let keys: Vec<String> = lua.load("return tui.store.keys()").eval().unwrap();
+      assert!(keys.is_empty());
+   }
+}
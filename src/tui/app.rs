@@ -1,11 +1,15 @@
-use crate::{TUIMutRef, TUIRef};
+use crate::{Migration, TUIMutRef, TUIRef};
 use ratatui::crossterm::event::Event;
 use ratatui::prelude::Buffer;
 
 pub trait App {
    const APP_NAME: &'static str;
    const CONFIG_FILE: Option<&'static str>;
+   // both the defaults and the user config return a table, the user's is merged over the
+   // defaults and read through `tui.cfg_get`. the defaults are evaluated again before
+   // every load and reload, so keep them free of side effects like timers or globals
    const DEFAULT_CONFIG_SRC: &'static str;
+   const CONFIG_VERSION: u32 = 0;
    fn init(tui: TUIMutRef) -> Self
    where
       Self: Sized;
@@ -15,6 +19,12 @@ pub trait App {
    fn render(&self, tui: TUIRef, buf: &mut Buffer)
    where
       Self: Sized;
+   fn migrations() -> Vec<Migration>
+   where
+      Self: Sized,
+   {
+      Vec::new()
+   }
 }
 
 pub enum AppOutput<T> {
@@ -25,9 +35,8 @@ pub enum AppOutput<T> {
 
 impl<T> AppOutput<T> {
    pub fn out(self) {
-      match self {
-         AppOutput::Err(e) => eprintln!("{}", e),
-         _ => {}
+      if let AppOutput::Err(e) = self {
+         eprintln!("{}", e);
       }
    }
    pub fn ok(t: T) -> Self {
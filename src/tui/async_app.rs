@@ -0,0 +1,166 @@
+use crate::{App, Migration, TUIMutRef, TUIRef};
+use ratatui::crossterm::event::Event;
+use ratatui::prelude::Buffer;
+use std::future::Future;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+
+// an app whose logic can await. run it with `TUI::<Async<MyApp>>::run_async()`.
+// `logic_async` always runs to the end, a tick that takes longer than its budget logs a
+// warning and the ticks it overlapped are skipped. slow work goes through `ctx.spawner`
+pub trait AsyncApp {
+   type Message: Send + 'static;
+   const APP_NAME: &'static str;
+   const CONFIG_FILE: Option<&'static str>;
+   const DEFAULT_CONFIG_SRC: &'static str;
+   const CONFIG_VERSION: u32 = 0;
+   fn init(tui: TUIMutRef) -> Self
+   where
+      Self: Sized;
+   fn logic_async(
+      &mut self,
+      tui: TUIMutRef<'_>,
+      event: Option<Event>,
+      ctx: AsyncCtx<Self::Message>,
+   ) -> impl Future<Output = ()>;
+   fn render(&self, tui: TUIRef, buf: &mut Buffer);
+   fn migrations() -> Vec<Migration>
+   where
+      Self: Sized,
+   {
+      Vec::new()
+   }
+}
+
+// adapts an AsyncApp to App so it can share the setup and render path
+pub struct Async<A: AsyncApp> {
+   pub app: A,
+}
+
+impl<A: AsyncApp> App for Async<A> {
+   const APP_NAME: &'static str = A::APP_NAME;
+   const CONFIG_FILE: Option<&'static str> = A::CONFIG_FILE;
+   const DEFAULT_CONFIG_SRC: &'static str = A::DEFAULT_CONFIG_SRC;
+   const CONFIG_VERSION: u32 = A::CONFIG_VERSION;
+
+   fn init(tui: TUIMutRef) -> Self {
+      Async { app: A::init(tui) }
+   }
+
+   // only reached when started with `TUI::run` instead of `TUI::run_async`
+   fn logic(&mut self, tui: TUIMutRef, _event: Option<Event>) {
+      tui.debug.error("async apps have to be started with TUI::run_async");
+      tui.runtime.request_exit();
+   }
+
+   fn render(&self, tui: TUIRef, buf: &mut Buffer) {
+      self.app.render(tui, buf);
+   }
+
+   fn migrations() -> Vec<Migration> {
+      A::migrations()
+   }
+}
+
+// messages that arrived since the last tick, and a way to start more work
+#[derive(Debug)]
+pub struct AsyncCtx<M> {
+   pub messages: Vec<M>,
+   pub spawner: Spawner<M>,
+}
+
+#[derive(Debug)]
+pub struct Spawner<M> {
+   tx: UnboundedSender<M>,
+}
+
+impl<M> Clone for Spawner<M> {
+   fn clone(&self) -> Self {
+      Self {
+         tx: self.tx.clone(),
+      }
+   }
+}
+
+impl<M: Send + 'static> Spawner<M> {
+   pub(crate) fn new(tx: UnboundedSender<M>) -> Self {
+      Self { tx }
+   }
+
+   // the future's output shows up in `AsyncCtx::messages` on a later tick
+   pub fn spawn<F>(&self, fut: F) -> JoinHandle<()>
+   where
+      F: Future<Output = M> + Send + 'static,
+   {
+      let tx = self.tx.clone();
+      tokio::spawn(async move {
+         let _ = tx.send(fut.await);
+      })
+   }
+   pub fn spawn_blocking<F>(&self, f: F) -> JoinHandle<()>
+   where
+      F: FnOnce() -> M + Send + 'static,
+   {
+      let tx = self.tx.clone();
+      tokio::task::spawn_blocking(move || {
+         let _ = tx.send(f());
+      })
+   }
+   pub fn send(&self, msg: M) {
+      let _ = self.tx.send(msg);
+   }
+   // for tasks that produce a stream of messages
+   pub fn sender(&self) -> UnboundedSender<M> {
+      self.tx.clone()
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::tui::TestTui;
+   use tokio::sync::mpsc::unbounded_channel;
+
+   struct Idle;
+
+   impl AsyncApp for Idle {
+      type Message = u32;
+      const APP_NAME: &'static str = "async_test";
+      const CONFIG_FILE: Option<&'static str> = None;
+      const DEFAULT_CONFIG_SRC: &'static str = "";
+      fn init(_tui: TUIMutRef) -> Self {
+         Idle
+      }
+      async fn logic_async(
+         &mut self,
+         _tui: TUIMutRef<'_>,
+         _event: Option<Event>,
+         _ctx: AsyncCtx<u32>,
+      ) {
+      }
+      fn render(&self, _tui: TUIRef, _buf: &mut Buffer) {}
+   }
+
+   #[tokio::test]
+   async fn spawned_work_comes_back_as_messages() {
+      let (tx, mut rx) = unbounded_channel();
+      let spawner = Spawner::new(tx);
+      spawner.spawn(async { 1 }).await.unwrap();
+      spawner.spawn_blocking(|| 2).await.unwrap();
+      spawner.send(3);
+      spawner.sender().send(4).unwrap();
+      let mut got = Vec::new();
+      while let Ok(msg) = rx.try_recv() {
+         got.push(msg);
+      }
+      assert_eq!(got, vec![1, 2, 3, 4]);
+   }
+
+   #[test]
+   fn running_without_run_async_exits() {
+      let mut t = TestTui::new();
+      let mut app = Async::<Idle>::init(t.tui());
+      app.logic(t.tui(), None);
+      assert!(!t.runtime.is_running());
+   }
+}
@@ -0,0 +1,318 @@
+use crate::tui::debug::Debug;
+use crate::{parse_color, LuaTable, MsgType, Runtime};
+use ratatui::prelude::*;
+use ratatui::widgets::Paragraph;
+use std::collections::HashMap;
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BarPosition {
+   Top,
+   Bottom,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BarSegment {
+   App,
+   Info,
+   Log,
+   Cfg,
+   Fn,
+   // text set by the app through `DebugBar::set_custom`
+   Custom(String),
+}
+
+impl BarSegment {
+   pub fn from_name(name: &str) -> Self {
+      match name {
+         "app" => BarSegment::App,
+         "info" => BarSegment::Info,
+         "log" => BarSegment::Log,
+         "cfg" => BarSegment::Cfg,
+         "fn" => BarSegment::Fn,
+         other => BarSegment::Custom(other.to_string()),
+      }
+   }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BarColors {
+   pub text: Color,
+   pub base: Color,
+   pub app: Color,
+   pub info: Color,
+   pub cfg: Color,
+   pub func: Color,
+}
+
+impl Default for BarColors {
+   fn default() -> Self {
+      Self {
+         text: Color::Black,
+         base: Color::LightMagenta,
+         app: Color::Magenta,
+         info: Color::White,
+         cfg: Color::White,
+         func: Color::Magenta,
+      }
+   }
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugBar {
+   pub position: BarPosition,
+   pub segments: Vec<BarSegment>,
+   pub colors: BarColors,
+   pub fn_width: usize,
+   custom: HashMap<String, (String, Option<Color>)>,
+}
+
+impl Default for DebugBar {
+   fn default() -> Self {
+      Self {
+         position: BarPosition::Bottom,
+         segments: vec![
+            BarSegment::App,
+            BarSegment::Info,
+            BarSegment::Log,
+            BarSegment::Cfg,
+            BarSegment::Fn,
+         ],
+         colors: BarColors::default(),
+         fn_width: 8,
+         custom: HashMap::new(),
+      }
+   }
+}
+
+impl DebugBar {
+   pub fn set_custom(&mut self, name: &str, text: &str) {
+      self.custom.insert(name.to_string(), (text.to_string(), None));
+   }
+   pub fn set_custom_colored(&mut self, name: &str, text: &str, bg: Color) {
+      self.custom.insert(name.to_string(), (text.to_string(), Some(bg)));
+   }
+   pub fn remove_custom(&mut self, name: &str) {
+      self.custom.remove(name);
+   }
+
+   // (bar row, what's left for everything else)
+   pub fn split(&self, area: Rect) -> (Rect, Rect) {
+      if area.height == 0 {
+         return (area, area);
+      }
+      let rest = Rect::new(area.x, area.y, area.width, area.height - 1);
+      match self.position {
+         BarPosition::Top => (
+            Rect::new(area.x, area.y, area.width, 1),
+            Rect { y: area.y + 1, ..rest },
+         ),
+         BarPosition::Bottom => (Rect::new(area.x, area.y + area.height - 1, area.width, 1), rest),
+      }
+   }
+
+   // reads `debug_bar = { position = "top", segments = { ... }, fn_width = 8, colors = { ... } }`,
+   // keys that are missing keep whatever the app set, returns a warning per bad value
+   pub(crate) fn apply_cfg(&mut self, table: &LuaTable) -> Vec<String> {
+      let mut warnings = Vec::new();
+      match table.get::<Option<String>>("position") {
+         Ok(Some(p)) if p == "top" => self.position = BarPosition::Top,
+         Ok(Some(p)) if p == "bottom" => self.position = BarPosition::Bottom,
+         Ok(None) => {}
+         _ => warnings.push("debug_bar.position should be \"top\" or \"bottom\"".to_string()),
+      }
+      match table.get::<Option<Vec<String>>>("segments") {
+         Ok(Some(segments)) => {
+            self.segments = segments.iter().map(|s| BarSegment::from_name(s)).collect()
+         }
+         Ok(None) => {}
+         Err(e) => warnings.push(format!("debug_bar.segments: {e}")),
+      }
+      match table.get::<Option<usize>>("fn_width") {
+         Ok(Some(w)) => self.fn_width = w,
+         Ok(None) => {}
+         Err(e) => warnings.push(format!("debug_bar.fn_width: {e}")),
+      }
+      if let Ok(Some(colors)) = table.get::<Option<LuaTable>>("colors") {
+         let slots = [
+            ("text", &mut self.colors.text),
+            ("base", &mut self.colors.base),
+            ("app", &mut self.colors.app),
+            ("info", &mut self.colors.info),
+            ("cfg", &mut self.colors.cfg),
+            ("fn", &mut self.colors.func),
+         ];
+         for (key, slot) in slots {
+            match colors.get::<Option<String>>(key) {
+               Ok(Some(c)) => match parse_color(&c) {
+                  Ok(color) => *slot = color,
+                  Err(e) => warnings.push(format!("debug_bar.colors.{key}: {e}")),
+               },
+               Ok(None) => {}
+               Err(e) => warnings.push(format!("debug_bar.colors.{key}: {e}")),
+            }
+         }
+      }
+      warnings
+   }
+
+   fn info_text(runtime: &Runtime) -> String {
+      let t_fps = runtime.target_fps();
+      let t_tps = runtime.target_tps();
+      format!(
+         " frame: {:0width_fps$} [{}/{}] tick: {:0width_tps$} [{}/{}] ",
+         runtime.frame() % t_fps.max(1),
+         runtime.fps() as u16,
+         t_fps,
+         runtime.tick() % t_tps.max(1),
+         runtime.tps() as u16,
+         t_tps,
+         width_fps = t_fps.to_string().len(),
+         width_tps = t_tps.to_string().len(),
+      )
+   }
+
+   fn fn_text(&self, debug: &Debug) -> String {
+      let fn_name_str = debug.current_fn.msg();
+      let fn_total_pad = self.fn_width.saturating_sub(fn_name_str.width());
+      let fn_left_pad = fn_total_pad / 2;
+      let fn_right_pad = fn_total_pad - fn_left_pad + 2;
+      format!(
+         " fn {}{}{}()",
+         " ".repeat(fn_left_pad),
+         fn_name_str,
+         " ".repeat(fn_right_pad)
+      )
+   }
+
+   fn log_text(debug: &Debug, width: usize) -> String {
+      let log_name_str = debug.current_log().map_or("", |l| l.msg.msg());
+      let log_title = " log: ";
+      let log_total_pad = width.saturating_sub(log_name_str.width() + log_title.len());
+      let log_left_pad = log_total_pad / 2;
+      let log_right_pad = log_total_pad - log_left_pad;
+      format!(
+         "{log_title}{}{}{}",
+         " ".repeat(log_left_pad),
+         log_name_str,
+         " ".repeat(log_right_pad)
+      )
+   }
+
+   pub(crate) fn render(
+      &self,
+      runtime: &Runtime,
+      debug: &Debug,
+      app_name: &str,
+      cfg_file: Option<&str>,
+      area: Rect,
+      buf: &mut Buffer,
+   ) {
+      let dbg_style = Style::default()
+         .bg(self.colors.base)
+         .fg(self.colors.text)
+         .add_modifier(Modifier::BOLD);
+      let log_color = debug
+         .current_log()
+         .map_or(MsgType::Info, |l| *l.msg.msg_type())
+         .color();
+
+      // everything but the log gets its natural width, the log fills the rest
+      let mut spans: Vec<Option<Span>> = self
+         .segments
+         .iter()
+         .map(|segment| match segment {
+            BarSegment::App => Some(Span::styled(
+               format!(" {app_name}: "),
+               dbg_style.bg(self.colors.app),
+            )),
+            BarSegment::Info => Some(Span::styled(
+               Self::info_text(runtime),
+               dbg_style.bg(self.colors.info),
+            )),
+            BarSegment::Cfg => Some(Span::styled(
+               match cfg_file {
+                  Some(c) => format!(" {c} -> "),
+                  None => " no cfg ".to_string(),
+               },
+               dbg_style.bg(self.colors.cfg),
+            )),
+            BarSegment::Fn => cfg_file.map(|_| {
+               Span::styled(self.fn_text(debug), dbg_style.bg(self.colors.func))
+            }),
+            BarSegment::Custom(name) => self.custom.get(name).map(|(text, bg)| {
+               Span::styled(format!(" {text} "), dbg_style.bg(bg.unwrap_or(self.colors.base)))
+            }),
+            BarSegment::Log => None,
+         })
+         .collect();
+
+      let used: usize = spans.iter().flatten().map(|s| s.width()).sum();
+      let pad = (area.width as usize).saturating_sub(used);
+      match self.segments.iter().position(|s| *s == BarSegment::Log) {
+         Some(i) => {
+            spans[i] = Some(Span::styled(
+               Self::log_text(debug, pad),
+               dbg_style.bg(log_color),
+            ))
+         }
+         None => spans.push(Some(Span::styled(" ".repeat(pad), dbg_style))),
+      }
+
+      Paragraph::new(Line::from(spans.into_iter().flatten().collect::<Vec<_>>()))
+         .render(area, buf);
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use mlua::Lua;
+
+   fn cfg(lua: &Lua, src: &str) -> LuaTable {
+      lua.load(src).eval().unwrap()
+   }
+
+   #[test]
+   fn the_bar_takes_one_row_at_either_edge() {
+      let mut bar = DebugBar::default();
+      let area = Rect::new(2, 3, 40, 10);
+      assert_eq!(bar.split(area), (Rect::new(2, 12, 40, 1), Rect::new(2, 3, 40, 9)));
+      bar.position = BarPosition::Top;
+      assert_eq!(bar.split(area), (Rect::new(2, 3, 40, 1), Rect::new(2, 4, 40, 9)));
+      let flat = Rect::new(0, 0, 40, 0);
+      assert_eq!(bar.split(flat), (flat, flat));
+   }
+
+   #[test]
+   fn config_sets_what_it_names_and_keeps_the_rest() {
+      let lua = Lua::new();
+      let mut bar = DebugBar::default();
+      bar.colors.app = Color::Blue;
+      let src = "{ position = 'top', segments = { 'log', 'fps' }, fn_width = 12, \
+                 colors = { base = 'red' } }";
+      assert!(bar.apply_cfg(&cfg(&lua, src)).is_empty());
+      assert_eq!(bar.position, BarPosition::Top);
+      assert_eq!(bar.segments, vec![BarSegment::Log, BarSegment::Custom("fps".into())]);
+      assert_eq!(bar.fn_width, 12);
+      assert_eq!(bar.colors.base, Color::Red);
+      assert_eq!(bar.colors.app, Color::Blue);
+   }
+
+   #[test]
+   fn bad_values_are_warned_about_and_skipped() {
+      let lua = Lua::new();
+      let mut bar = DebugBar::default();
+      let src = "{ position = 'left', fn_width = 'wide', \
+                 colors = { base = 'nope', fn = 'green' } }";
+      let warnings = bar.apply_cfg(&cfg(&lua, src));
+      assert_eq!(warnings.len(), 3, "{warnings:?}");
+      assert!(warnings[0].starts_with("debug_bar.position"));
+      assert!(warnings[1].starts_with("debug_bar.fn_width"));
+      assert!(warnings[2].starts_with("debug_bar.colors.base"));
+      assert_eq!(bar.position, BarPosition::Bottom);
+      assert_eq!(bar.fn_width, 8);
+      assert_eq!(bar.colors.base, BarColors::default().base);
+      assert_eq!(bar.colors.func, Color::Green);
+   }
+}
@@ -0,0 +1,195 @@
+use crate::tui::task::in_task;
+use crate::MsgType;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::fd::FromRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptureStream {
+   Stdout,
+   Stderr,
+}
+
+impl CaptureStream {
+   pub fn level(&self) -> MsgType {
+      match self {
+         CaptureStream::Stdout => MsgType::Info,
+         CaptureStream::Stderr => MsgType::Warn,
+      }
+   }
+}
+
+type Lines = Arc<Mutex<Vec<(CaptureStream, String)>>>;
+
+// swaps fd 1 and 2 for pipes so stray prints can't scribble over the alternate screen
+#[derive(Debug)]
+pub(crate) struct Capture {
+   // the real stdout / stderr, -1 once closed
+   saved: [i32; 2],
+   active: Arc<AtomicBool>,
+   done: Vec<Receiver<()>>,
+   lines: Lines,
+}
+
+impl Capture {
+   #[cfg(unix)]
+   pub(crate) fn start() -> Option<Self> {
+      let _ = io::stdout().flush();
+      let _ = io::stderr().flush();
+      let mut capture = Self {
+         saved: [-1, -1],
+         active: Arc::new(AtomicBool::new(true)),
+         done: Vec::new(),
+         lines: Lines::default(),
+      };
+      let targets = [
+         (libc::STDOUT_FILENO, CaptureStream::Stdout),
+         (libc::STDERR_FILENO, CaptureStream::Stderr),
+      ];
+      for (i, (fd, stream)) in targets.into_iter().enumerate() {
+         let mut pipe = [0; 2];
+         // SAFETY: only touches fds this function just got back from the kernel,
+         // on failure Drop puts back whatever was already swapped
+         let reader = unsafe {
+            if libc::pipe(pipe.as_mut_ptr()) != 0 {
+               return None;
+            }
+            capture.saved[i] = libc::dup(fd);
+            if capture.saved[i] < 0 || libc::dup2(pipe[1], fd) < 0 {
+               libc::close(pipe[0]);
+               libc::close(pipe[1]);
+               return None;
+            }
+            libc::close(pipe[1]);
+            File::from_raw_fd(pipe[0])
+         };
+         capture
+            .done
+            .push(spawn_reader(reader, stream, capture.lines.clone()));
+      }
+      capture.install_panic_hook();
+      Some(capture)
+   }
+
+   #[cfg(not(unix))]
+   pub(crate) fn start() -> Option<Self> {
+      None
+   }
+
+   // a panic message should land on the real stderr, not in a pipe nobody reads anymore
+   fn install_panic_hook(&self) {
+      let saved = self.saved;
+      let active = self.active.clone();
+      let prev = std::panic::take_hook();
+      std::panic::set_hook(Box::new(move |info| {
+         // caught panics on the task pool don't end the app
+         if !in_task() && active.swap(false, Ordering::SeqCst) {
+            restore_fds(saved);
+         }
+         prev(info);
+      }));
+   }
+
+   // a handle to the real stdout for the terminal backend
+   #[cfg(unix)]
+   pub(crate) fn tty(&self) -> Option<File> {
+      // SAFETY: dup of an fd owned by this capture, the new fd is owned by the File
+      let fd = unsafe { libc::dup(self.saved[0]) };
+      match fd < 0 {
+         true => None,
+         false => Some(unsafe { File::from_raw_fd(fd) }),
+      }
+   }
+
+   #[cfg(not(unix))]
+   pub(crate) fn tty(&self) -> Option<File> {
+      None
+   }
+
+   pub(crate) fn drain(&self) -> Vec<(CaptureStream, String)> {
+      match self.lines.lock() {
+         Ok(mut lines) => lines.drain(..).collect(),
+         Err(_) => Vec::new(),
+      }
+   }
+
+   // puts the real fds back and returns whatever was captured but never drained
+   pub(crate) fn stop(&mut self) -> Vec<(CaptureStream, String)> {
+      let _ = io::stdout().flush();
+      let _ = io::stderr().flush();
+      if self.active.swap(false, Ordering::SeqCst) {
+         restore_fds(self.saved);
+      }
+      close_fds(&mut self.saved);
+      // readers see eof once the write ends are gone, unless a child process kept one
+      for done in self.done.drain(..) {
+         let _ = done.recv_timeout(Duration::from_millis(100));
+      }
+      self.drain()
+   }
+}
+
+impl Drop for Capture {
+   fn drop(&mut self) {
+      self.stop();
+   }
+}
+
+fn spawn_reader(file: File, stream: CaptureStream, lines: Lines) -> Receiver<()> {
+   let (done_tx, done_rx) = mpsc::channel();
+   thread::spawn(move || {
+      let mut reader = BufReader::new(file);
+      let mut buf = Vec::new();
+      loop {
+         buf.clear();
+         match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+               let line = String::from_utf8_lossy(&buf);
+               let line = line.trim_end_matches(['\n', '\r']).to_string();
+               if let Ok(mut lines) = lines.lock() {
+                  lines.push((stream, line));
+               }
+            }
+         }
+      }
+      let _ = done_tx.send(());
+   });
+   done_rx
+}
+
+#[cfg(unix)]
+fn restore_fds(saved: [i32; 2]) {
+   // SAFETY: dup2 onto the std fds, a stale source fd only makes it fail
+   unsafe {
+      if saved[0] >= 0 {
+         libc::dup2(saved[0], libc::STDOUT_FILENO);
+      }
+      if saved[1] >= 0 {
+         libc::dup2(saved[1], libc::STDERR_FILENO);
+      }
+   }
+}
+
+#[cfg(not(unix))]
+fn restore_fds(_saved: [i32; 2]) {}
+
+#[cfg(unix)]
+fn close_fds(saved: &mut [i32; 2]) {
+   for fd in saved.iter_mut().filter(|fd| **fd >= 0) {
+      // SAFETY: the saved fds are owned by the capture and closed exactly once
+      unsafe {
+         libc::close(*fd);
+      }
+      *fd = -1;
+   }
+}
+
+#[cfg(not(unix))]
+fn close_fds(_saved: &mut [i32; 2]) {}
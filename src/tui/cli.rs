@@ -0,0 +1,119 @@
+use crate::tui::{diff_cfg, reset_cfg, write_new_cfg};
+use crate::{app_err, App, AppOutput, ColorSupport};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CliAction {
+   Run,
+   CfgDiff,
+   CfgNew,
+   CfgReset,
+}
+
+impl CliAction {
+   // strips built-in flags so apps only ever see their own args
+   pub(crate) fn take(args: &mut Vec<String>) -> AppOutput<Self> {
+      let mut found = Vec::new();
+      args.retain(|arg| {
+         let action = match arg.as_str() {
+            "--cfg-diff" => CliAction::CfgDiff,
+            "--cfg-new" => CliAction::CfgNew,
+            "--cfg-reset" => CliAction::CfgReset,
+            _ => return true,
+         };
+         found.push((arg.clone(), action));
+         false
+      });
+      match found.as_slice() {
+         [] => AppOutput::ok(CliAction::Run),
+         [(_, action)] => AppOutput::ok(*action),
+         _ => {
+            let flags: Vec<_> = found.iter().map(|(flag, _)| flag.as_str()).collect();
+            app_err!("only one config action can run at a time, got {}", flags.join(" "))
+         }
+      }
+   }
+
+   pub(crate) fn exec<A: App>(self) -> AppOutput<()> {
+      match self {
+         CliAction::Run => AppOutput::void(),
+         CliAction::CfgDiff => match diff_cfg::<A>() {
+            AppOutput::Ok(diff) if diff.is_empty() => {
+               println!("{} config is up to date", A::APP_NAME);
+               AppOutput::void()
+            }
+            AppOutput::Ok(diff) => {
+               print!("{diff}");
+               AppOutput::void()
+            }
+            AppOutput::Err(e) => AppOutput::Err(e),
+            AppOutput::Nil => AppOutput::void(),
+         },
+         CliAction::CfgNew => match write_new_cfg::<A>() {
+            AppOutput::Ok(path) => {
+               println!("wrote default config to {}", path.to_string_lossy());
+               AppOutput::void()
+            }
+            AppOutput::Err(e) => AppOutput::Err(e),
+            AppOutput::Nil => AppOutput::void(),
+         },
+         CliAction::CfgReset => match reset_cfg::<A>() {
+            AppOutput::Ok(backup) => {
+               println!(
+                  "reset config to defaults, old config saved to {}",
+                  backup.to_string_lossy()
+               );
+               AppOutput::void()
+            }
+            AppOutput::Err(e) => AppOutput::Err(e),
+            AppOutput::Nil => AppOutput::void(),
+         },
+      }
+   }
+}
+
+// `--color=truecolor|256|16|none` overrides what the environment says
+pub(crate) fn take_color_flag(args: &mut Vec<String>) -> Option<ColorSupport> {
+   let mut support = None;
+   args.retain(|arg| match arg.strip_prefix("--color=") {
+      Some(name) => {
+         support = ColorSupport::from_name(name);
+         support.is_none()
+      }
+      None => true,
+   });
+   support
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn args(args: &[&str]) -> Vec<String> {
+      args.iter().map(|a| a.to_string()).collect()
+   }
+
+   #[test]
+   fn take_strips_the_action() {
+      let mut a = args(&["x", "--cfg-diff", "y"]);
+      assert!(matches!(CliAction::take(&mut a), AppOutput::Ok(CliAction::CfgDiff)));
+      assert_eq!(a, args(&["x", "y"]));
+      let mut a = args(&["x"]);
+      assert!(matches!(CliAction::take(&mut a), AppOutput::Ok(CliAction::Run)));
+   }
+
+   #[test]
+   fn take_rejects_several_actions() {
+      let mut a = args(&["--cfg-new", "--cfg-reset"]);
+      assert!(matches!(CliAction::take(&mut a), AppOutput::Err(_)));
+   }
+
+   #[test]
+   fn color_flag_is_taken() {
+      let mut a = args(&["--color=256", "x"]);
+      assert_eq!(take_color_flag(&mut a), Some(ColorSupport::Ansi256));
+      assert_eq!(a, args(&["x"]));
+      let mut a = args(&["--color=bogus"]);
+      assert_eq!(take_color_flag(&mut a), None);
+      assert_eq!(a, args(&["--color=bogus"]));
+   }
+}
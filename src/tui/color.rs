@@ -0,0 +1,227 @@
+use ratatui::buffer::Buffer;
+use ratatui::prelude::Color;
+use std::env;
+
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// xterm's defaults for the 16 base colors
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+   (Color::Black, (0, 0, 0)),
+   (Color::Red, (205, 0, 0)),
+   (Color::Green, (0, 205, 0)),
+   (Color::Yellow, (205, 205, 0)),
+   (Color::Blue, (0, 0, 238)),
+   (Color::Magenta, (205, 0, 205)),
+   (Color::Cyan, (0, 205, 205)),
+   (Color::Gray, (229, 229, 229)),
+   (Color::DarkGray, (127, 127, 127)),
+   (Color::LightRed, (255, 0, 0)),
+   (Color::LightGreen, (0, 255, 0)),
+   (Color::LightYellow, (255, 255, 0)),
+   (Color::LightBlue, (92, 92, 255)),
+   (Color::LightMagenta, (255, 0, 255)),
+   (Color::LightCyan, (0, 255, 255)),
+   (Color::White, (255, 255, 255)),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+   None,
+   Ansi16,
+   Ansi256,
+   TrueColor,
+}
+
+impl ColorSupport {
+   pub fn detect() -> Self {
+      Self::detect_with(|name| env::var(name).ok())
+   }
+
+   fn detect_with(var: impl Fn(&str) -> Option<String>) -> Self {
+      if var("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+         return ColorSupport::None;
+      }
+      let colorterm = var("COLORTERM").unwrap_or_default().to_lowercase();
+      if colorterm == "truecolor" || colorterm == "24bit" {
+         return ColorSupport::TrueColor;
+      }
+      let term = var("TERM").unwrap_or_default().to_lowercase();
+      match term.as_str() {
+         "dumb" => ColorSupport::None,
+         t if t.contains("truecolor") || t.contains("24bit") || t.contains("direct") => {
+            ColorSupport::TrueColor
+         }
+         t if t.contains("256") => ColorSupport::Ansi256,
+         // windows terminals don't set TERM but handle rgb just fine
+         "" if cfg!(windows) || var("WT_SESSION").is_some() => ColorSupport::TrueColor,
+         _ => ColorSupport::Ansi16,
+      }
+   }
+
+   pub fn from_name(name: &str) -> Option<Self> {
+      match name.to_lowercase().as_str() {
+         "truecolor" | "24bit" | "rgb" => Some(ColorSupport::TrueColor),
+         "256" | "ansi256" => Some(ColorSupport::Ansi256),
+         "16" | "ansi16" | "ansi" => Some(ColorSupport::Ansi16),
+         "none" | "off" | "never" => Some(ColorSupport::None),
+         _ => None,
+      }
+   }
+
+   pub fn convert(&self, color: Color) -> Color {
+      match (self, color) {
+         (ColorSupport::TrueColor, c) | (_, c @ Color::Reset) => c,
+         (ColorSupport::None, _) => Color::Reset,
+         (ColorSupport::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(nearest_256(r, g, b)),
+         (ColorSupport::Ansi256, c) => c,
+         (ColorSupport::Ansi16, Color::Rgb(r, g, b)) => nearest_16(r, g, b),
+         (ColorSupport::Ansi16, Color::Indexed(i)) if i < 16 => ANSI16[i as usize].0,
+         (ColorSupport::Ansi16, Color::Indexed(i)) => {
+            let (r, g, b) = indexed_rgb(i);
+            nearest_16(r, g, b)
+         }
+         (ColorSupport::Ansi16, c) => c,
+      }
+   }
+
+   pub fn downsample(&self, buf: &mut Buffer) {
+      if *self == ColorSupport::TrueColor {
+         return;
+      }
+      for cell in buf.content.iter_mut() {
+         cell.fg = self.convert(cell.fg);
+         cell.bg = self.convert(cell.bg);
+         cell.underline_color = self.convert(cell.underline_color);
+      }
+   }
+}
+
+// rgb value of any concrete color, base colors use xterm's defaults
+pub fn color_rgb(color: Color) -> Option<(u8, u8, u8)> {
+   match color {
+      Color::Rgb(r, g, b) => Some((r, g, b)),
+      Color::Indexed(i) => Some(indexed_rgb(i)),
+      Color::Reset => None,
+      c => ANSI16.iter().find(|(named, _)| *named == c).map(|(_, rgb)| *rgb),
+   }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+   let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+   d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn indexed_rgb(i: u8) -> (u8, u8, u8) {
+   match i {
+      0..=15 => ANSI16[i as usize].1,
+      16..=231 => {
+         let i = i - 16;
+         (CUBE[(i / 36) as usize], CUBE[((i / 6) % 6) as usize], CUBE[(i % 6) as usize])
+      }
+      _ => {
+         let v = 8 + (i - 232) * 10;
+         (v, v, v)
+      }
+   }
+}
+
+// best of the 6x6x6 cube and the gray ramp, the 16 base colors vary too much between terminals
+fn nearest_256(r: u8, g: u8, b: u8) -> u8 {
+   let level = |v: u8| {
+      (0..CUBE.len())
+         .min_by_key(|i| (CUBE[*i] as i32 - v as i32).abs())
+         .unwrap_or(0) as u8
+   };
+   let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+
+   let avg = (r as u32 + g as u32 + b as u32) / 3;
+   let gray = 232 + ((avg.saturating_sub(3)) / 10).min(23) as u8;
+
+   let target = (r, g, b);
+   match distance(indexed_rgb(gray), target) < distance(indexed_rgb(cube), target) {
+      true => gray,
+      false => cube,
+   }
+}
+
+fn nearest_16(r: u8, g: u8, b: u8) -> Color {
+   ANSI16
+      .iter()
+      .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+      .map_or(Color::Reset, |(c, _)| *c)
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use ratatui::layout::Rect;
+
+   fn detect(vars: &[(&str, &str)]) -> ColorSupport {
+      ColorSupport::detect_with(|name| {
+         vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+      })
+   }
+
+   #[test]
+   fn detection_reads_no_color_colorterm_and_term() {
+      let truecolor = [("COLORTERM", "truecolor"), ("TERM", "xterm-256color")];
+      assert_eq!(detect(&truecolor), ColorSupport::TrueColor);
+      assert_eq!(detect(&[("NO_COLOR", "1"), ("COLORTERM", "24bit")]), ColorSupport::None);
+      assert_eq!(detect(&[("NO_COLOR", ""), ("TERM", "xterm-256color")]), ColorSupport::Ansi256);
+      assert_eq!(detect(&[("TERM", "xterm-direct")]), ColorSupport::TrueColor);
+      assert_eq!(detect(&[("TERM", "dumb")]), ColorSupport::None);
+      assert_eq!(detect(&[("TERM", "xterm")]), ColorSupport::Ansi16);
+   }
+
+   #[test]
+   fn names_parse_case_insensitively() {
+      assert_eq!(ColorSupport::from_name("TrueColor"), Some(ColorSupport::TrueColor));
+      assert_eq!(ColorSupport::from_name("256"), Some(ColorSupport::Ansi256));
+      assert_eq!(ColorSupport::from_name("ansi"), Some(ColorSupport::Ansi16));
+      assert_eq!(ColorSupport::from_name("off"), Some(ColorSupport::None));
+      assert_eq!(ColorSupport::from_name("lots"), None);
+   }
+
+   #[test]
+   fn grays_use_the_ramp_and_colors_the_cube() {
+      assert_eq!(nearest_256(128, 128, 128), 244);
+      assert_eq!(nearest_256(255, 0, 0), 196);
+      assert_eq!(nearest_256(0, 0, 0), 16);
+      assert_eq!(nearest_256(95, 135, 175), 67);
+   }
+
+   #[test]
+   fn sixteen_colors_pick_the_closest_base_color() {
+      assert_eq!(nearest_16(250, 10, 10), Color::LightRed);
+      assert_eq!(nearest_16(200, 0, 0), Color::Red);
+      assert_eq!(nearest_16(130, 130, 130), Color::DarkGray);
+      let ansi16 = ColorSupport::Ansi16;
+      assert_eq!(ansi16.convert(Color::Indexed(1)), Color::Red);
+      assert_eq!(ansi16.convert(Color::Indexed(196)), Color::LightRed);
+      assert_eq!(ansi16.convert(Color::Blue), Color::Blue);
+   }
+
+   #[test]
+   fn convert_follows_the_support_level() {
+      let red = Color::Rgb(255, 0, 0);
+      assert_eq!(ColorSupport::TrueColor.convert(red), red);
+      assert_eq!(ColorSupport::Ansi256.convert(red), Color::Indexed(196));
+      assert_eq!(ColorSupport::Ansi256.convert(Color::Cyan), Color::Cyan);
+      assert_eq!(ColorSupport::None.convert(red), Color::Reset);
+      assert_eq!(ColorSupport::None.convert(Color::Reset), Color::Reset);
+   }
+
+   #[test]
+   fn downsample_rewrites_every_cell() {
+      let mut buf = Buffer::empty(Rect::new(0, 0, 2, 1));
+      for cell in buf.content.iter_mut() {
+         cell.fg = Color::Rgb(255, 0, 0);
+         cell.bg = Color::Rgb(0, 0, 238);
+      }
+      let before = buf.clone();
+      ColorSupport::TrueColor.downsample(&mut buf);
+      assert_eq!(buf, before);
+      ColorSupport::Ansi16.downsample(&mut buf);
+      assert!(buf.content.iter().all(|c| c.fg == Color::LightRed && c.bg == Color::Blue));
+   }
+}
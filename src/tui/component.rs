@@ -0,0 +1,408 @@
+use crate::{App, Migration, TUIMutRef, TUIRef};
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{Event, KeyCode, KeyEventKind, MouseButton, MouseEventKind};
+use ratatui::layout::{Position, Rect};
+use ratatui::style::Style;
+use ratatui::widgets::Block;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventResult {
+   Ignored,
+   Handled,
+}
+
+// a widget with its own state. parents own their children, lay them out in `render` and
+// hand them to the tree through `children` / `children_mut` so focus can find them
+pub trait Component {
+   fn id(&self) -> &str;
+   fn focusable(&self) -> bool {
+      false
+   }
+   fn children(&self) -> Vec<&dyn Component> {
+      Vec::new()
+   }
+   fn children_mut(&mut self) -> Vec<&mut dyn Component> {
+      Vec::new()
+   }
+   // ignored events bubble up to the parent
+   fn handle(&mut self, _tui: TUIMutRef, _event: &Event) -> EventResult {
+      EventResult::Ignored
+   }
+   fn render(&self, ctx: &RenderCtx, area: Rect, buf: &mut Buffer);
+}
+
+// a root component that is the whole app, run it with `TUI::<Components<MyRoot>>::run()`
+pub trait ComponentApp: Component {
+   const APP_NAME: &'static str;
+   const CONFIG_FILE: Option<&'static str>;
+   const DEFAULT_CONFIG_SRC: &'static str;
+   const CONFIG_VERSION: u32 = 0;
+   fn init(tui: TUIMutRef) -> Self
+   where
+      Self: Sized;
+   // every tick before the event is routed, for timers, tasks and other polling
+   fn tick(&mut self, _tui: TUIMutRef) {}
+   fn migrations() -> Vec<Migration>
+   where
+      Self: Sized,
+   {
+      Vec::new()
+   }
+}
+
+pub struct RenderCtx<'a> {
+   pub tui: TUIRef<'a>,
+   focused: Option<&'a str>,
+   areas: &'a RefCell<HashMap<String, Rect>>,
+}
+
+impl RenderCtx<'_> {
+   pub fn is_focused(&self, id: &str) -> bool {
+      self.focused == Some(id)
+   }
+   // the theme's focus color on the focused component, its border color everywhere else
+   pub fn focus_style(&self, id: &str) -> Style {
+      match self.is_focused(id) {
+         true => self.tui.theme.style("focus"),
+         false => self.tui.theme.style("border"),
+      }
+   }
+   pub fn block(&self, id: &str) -> Block<'static> {
+      Block::bordered().border_style(self.focus_style(id))
+   }
+   // children go through here so clicks can find them
+   pub fn render_child(&self, child: &dyn Component, area: Rect, buf: &mut Buffer) {
+      self.areas.borrow_mut().insert(child.id().to_string(), area);
+      child.render(self, area, buf);
+   }
+}
+
+// owns the root component and tracks focus across the whole tree
+pub struct Components<C: Component> {
+   pub root: C,
+   focused: Option<String>,
+   areas: RefCell<HashMap<String, Rect>>,
+}
+
+impl<C: Component> Components<C> {
+   pub fn new(root: C) -> Self {
+      let mut components = Self {
+         root,
+         focused: None,
+         areas: RefCell::new(HashMap::new()),
+      };
+      components.focused = components.focus_order().first().cloned();
+      components
+   }
+
+   // focusable ids, depth first, the order tab walks through
+   pub fn focus_order(&self) -> Vec<String> {
+      fn walk(c: &dyn Component, ids: &mut Vec<String>) {
+         if c.focusable() {
+            ids.push(c.id().to_string());
+         }
+         for child in c.children() {
+            walk(child, ids);
+         }
+      }
+      let mut ids = Vec::new();
+      walk(&self.root, &mut ids);
+      ids
+   }
+
+   // the tree can change under the focused id, a component that's gone has no focus
+   pub fn focused(&self) -> Option<&str> {
+      let id = self.focused.as_deref()?;
+      self.focus_order().iter().any(|f| f == id).then_some(id)
+   }
+   pub fn is_focused(&self, id: &str) -> bool {
+      self.focused() == Some(id)
+   }
+   fn drop_stale_focus(&mut self) {
+      if self.focused.is_some() && self.focused().is_none() {
+         self.focused = None;
+      }
+   }
+   pub fn focus(&mut self, id: &str) -> bool {
+      match self.focus_order().iter().any(|f| f == id) {
+         true => {
+            self.focused = Some(id.to_string());
+            true
+         }
+         false => false,
+      }
+   }
+   pub fn blur(&mut self) {
+      self.focused = None;
+   }
+   pub fn focus_next(&mut self) {
+      self.step_focus(1);
+   }
+   pub fn focus_prev(&mut self) {
+      self.step_focus(-1);
+   }
+   fn step_focus(&mut self, step: isize) {
+      let order = self.focus_order();
+      if order.is_empty() {
+         self.focused = None;
+         return;
+      }
+      let len = order.len() as isize;
+      let next = match self.focused.as_ref().and_then(|f| order.iter().position(|o| o == f)) {
+         Some(i) => (i as isize + step).rem_euclid(len),
+         None if step < 0 => len - 1,
+         None => 0,
+      };
+      self.focused = Some(order[next as usize].clone());
+   }
+
+   // keys go to the focused component and bubble up from there, tab / shift-tab move focus
+   // when nothing used them. a left click focuses the component under the cursor
+   pub fn handle(&mut self, mut tui: TUIMutRef, event: &Event) -> EventResult {
+      self.drop_stale_focus();
+      if let Event::Mouse(mouse) = event
+         && let MouseEventKind::Down(MouseButton::Left) = mouse.kind
+         && let Some(id) = self.at(Position::new(mouse.column, mouse.row))
+      {
+         self.focus(&id);
+      }
+      let routed = match self.focused.clone() {
+         Some(id) => route(&mut self.root, &id, &mut tui, event),
+         None => None,
+      };
+      let result = match routed {
+         Some(result) => result,
+         None => self.root.handle(tui.reborrow(), event),
+      };
+      if result == EventResult::Handled {
+         return result;
+      }
+      match event {
+         Event::Key(key) if key.kind != KeyEventKind::Release => match key.code {
+            KeyCode::Tab => self.focus_next(),
+            KeyCode::BackTab => self.focus_prev(),
+            _ => return EventResult::Ignored,
+         },
+         _ => return EventResult::Ignored,
+      }
+      EventResult::Handled
+   }
+
+   // the focusable component drawn under `pos`, innermost first
+   fn at(&self, pos: Position) -> Option<String> {
+      let areas = self.areas.borrow();
+      self
+         .focus_order()
+         .into_iter()
+         .rev()
+         .find(|id| areas.get(id).is_some_and(|a| a.contains(pos)))
+   }
+
+   pub fn render(&self, tui: TUIRef, area: Rect, buf: &mut Buffer) {
+      self.areas.borrow_mut().clear();
+      let ctx = RenderCtx {
+         tui,
+         focused: self.focused(),
+         areas: &self.areas,
+      };
+      ctx.render_child(&self.root, area, buf);
+   }
+}
+
+impl<C: ComponentApp> App for Components<C> {
+   const APP_NAME: &'static str = C::APP_NAME;
+   const CONFIG_FILE: Option<&'static str> = C::CONFIG_FILE;
+   const DEFAULT_CONFIG_SRC: &'static str = C::DEFAULT_CONFIG_SRC;
+   const CONFIG_VERSION: u32 = C::CONFIG_VERSION;
+
+   // clicks move focus, so component apps always want the mouse
+   fn init(tui: TUIMutRef) -> Self {
+      tui.runtime.capture_mouse(true);
+      Components::new(C::init(tui))
+   }
+   fn logic(&mut self, mut tui: TUIMutRef, event: Option<Event>) {
+      self.root.tick(tui.reborrow());
+      if let Some(event) = event {
+         self.handle(tui, &event);
+      }
+   }
+   fn render(&self, tui: TUIRef, buf: &mut Buffer) {
+      let area = buf.area;
+      Components::render(self, tui, area, buf);
+   }
+   fn migrations() -> Vec<Migration> {
+      C::migrations()
+   }
+}
+
+// None if `id` isn't in this subtree, otherwise the result after bubbling
+fn route(
+   c: &mut dyn Component,
+   id: &str,
+   tui: &mut TUIMutRef,
+   event: &Event,
+) -> Option<EventResult> {
+   if c.id() == id {
+      return Some(c.handle(tui.reborrow(), event));
+   }
+   let mut found = None;
+   for child in c.children_mut() {
+      if let Some(result) = route(child, id, tui, event) {
+         found = Some(result);
+         break;
+      }
+   }
+   match found? {
+      EventResult::Handled => Some(EventResult::Handled),
+      EventResult::Ignored => Some(c.handle(tui.reborrow(), event)),
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::tui::TestTui;
+   use ratatui::crossterm::event::{KeyEvent, KeyModifiers, MouseEvent};
+
+   // handles the keys in `keys`, logs everything that reaches it
+   struct Node {
+      id: &'static str,
+      focusable: bool,
+      keys: &'static str,
+      seen: Vec<char>,
+      children: Vec<Node>,
+   }
+
+   fn node(id: &'static str, focusable: bool, keys: &'static str, children: Vec<Node>) -> Node {
+      Node {
+         id,
+         focusable,
+         keys,
+         seen: Vec::new(),
+         children,
+      }
+   }
+
+   impl Component for Node {
+      fn id(&self) -> &str {
+         self.id
+      }
+      fn focusable(&self) -> bool {
+         self.focusable
+      }
+      fn children(&self) -> Vec<&dyn Component> {
+         self.children.iter().map(|c| c as &dyn Component).collect()
+      }
+      fn children_mut(&mut self) -> Vec<&mut dyn Component> {
+         self.children.iter_mut().map(|c| c as &mut dyn Component).collect()
+      }
+      fn handle(&mut self, _tui: TUIMutRef, event: &Event) -> EventResult {
+         if let Event::Key(KeyEvent { code: KeyCode::Char(c), .. }) = event {
+            self.seen.push(*c);
+            if self.keys.contains(*c) {
+               return EventResult::Handled;
+            }
+         }
+         EventResult::Ignored
+      }
+      // children stacked one row each below their parent
+      fn render(&self, ctx: &RenderCtx, area: Rect, buf: &mut Buffer) {
+         for (i, child) in self.children.iter().enumerate() {
+            let row = Rect { y: area.y + 1 + i as u16, height: 1, ..area };
+            ctx.render_child(child, row, buf);
+         }
+      }
+   }
+
+   fn tree() -> Components<Node> {
+      let inner = node("inner", true, "x", vec![]);
+      let panel = node("panel", true, "y", vec![inner]);
+      let other = node("other", true, "", vec![]);
+      Components::new(node("root", false, "z", vec![panel, other]))
+   }
+   fn key(code: KeyCode) -> Event {
+      Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+   }
+
+   #[test]
+   fn focus_walks_the_tree_depth_first() {
+      let mut c = tree();
+      assert_eq!(c.focus_order(), vec!["panel", "inner", "other"]);
+      assert_eq!(c.focused(), Some("panel"));
+      c.focus_next();
+      c.focus_next();
+      assert_eq!(c.focused(), Some("other"));
+      c.focus_next();
+      assert_eq!(c.focused(), Some("panel"));
+      c.focus_prev();
+      assert_eq!(c.focused(), Some("other"));
+      assert!(!c.focus("root"));
+      c.blur();
+      c.focus_prev();
+      assert_eq!(c.focused(), Some("other"));
+   }
+
+   #[test]
+   fn keys_bubble_from_the_focused_component() {
+      let mut t = TestTui::new();
+      let mut c = tree();
+      c.focus("inner");
+      assert_eq!(c.handle(t.tui(), &key(KeyCode::Char('y'))), EventResult::Handled);
+      assert_eq!(c.handle(t.tui(), &key(KeyCode::Char('q'))), EventResult::Ignored);
+      let panel = &c.root.children[0];
+      assert_eq!(panel.children[0].seen, vec!['y', 'q']);
+      assert_eq!(panel.seen, vec!['y', 'q']);
+      assert_eq!(c.root.seen, vec!['q']);
+      assert_eq!(c.root.children[1].seen, Vec::<char>::new());
+      assert_eq!(c.handle(t.tui(), &key(KeyCode::Tab)), EventResult::Handled);
+      assert_eq!(c.focused(), Some("other"));
+   }
+
+   #[test]
+   fn removed_components_lose_focus() {
+      let mut t = TestTui::new();
+      let mut c = tree();
+      c.focus("other");
+      c.root.children.pop();
+      assert_eq!(c.focused(), None);
+      c.handle(t.tui(), &key(KeyCode::Char('z')));
+      assert_eq!(c.root.seen, vec!['z']);
+      assert_eq!(c.focused, None);
+   }
+
+   #[test]
+   fn clicks_focus_what_was_drawn_there() {
+      let mut t = TestTui::new();
+      let mut c = tree();
+      c.focus("other");
+      let area = Rect::new(0, 0, 10, 4);
+      c.render(t.tui_ref(), area, &mut Buffer::empty(area));
+      let click = Event::Mouse(MouseEvent {
+         kind: MouseEventKind::Down(MouseButton::Left),
+         column: 3,
+         row: 1,
+         modifiers: KeyModifiers::NONE,
+      });
+      c.handle(t.tui(), &click);
+      assert_eq!(c.focused(), Some("panel"));
+   }
+
+   impl ComponentApp for Node {
+      const APP_NAME: &'static str = "components";
+      const CONFIG_FILE: Option<&'static str> = None;
+      const DEFAULT_CONFIG_SRC: &'static str = "";
+      fn init(_tui: TUIMutRef) -> Self {
+         node("root", false, "", vec![])
+      }
+   }
+
+   #[test]
+   fn component_apps_capture_the_mouse() {
+      let mut t = TestTui::new();
+      assert!(!t.runtime.is_mouse_captured());
+      let _app = <Components<Node> as App>::init(t.tui());
+      assert!(t.runtime.is_mouse_captured());
+   }
+}
@@ -0,0 +1,551 @@
+use crate::tui::Cfg;
+use crate::tui::debug::Debug;
+use crate::{LogSink, LogSource, LuaMultiValue, LuaTable, LuaValue, MsgType};
+use ratatui::crossterm::event::{
+   Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEventKind,
+};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Clear, Paragraph};
+use std::cell::Cell;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const LEVELS: [MsgType; 6] = [
+   MsgType::Trace,
+   MsgType::Debug,
+   MsgType::Info,
+   MsgType::Event,
+   MsgType::Warn,
+   MsgType::Error,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsoleLayout {
+   Full,
+   Split,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ConsoleMode {
+   Browse,
+   Search,
+   Repl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ConsoleInput {
+   Ignored,
+   Handled,
+   Close,
+}
+
+#[derive(Debug)]
+pub struct Console {
+   layout: ConsoleLayout,
+   min_level: MsgType,
+   search: String,
+   mode: ConsoleMode,
+   // rows scrolled up from the newest entry, 0 follows the tail
+   scroll: usize,
+   // (total rows, visible rows) of the last render, used to clamp scrolling
+   view: Cell<(usize, usize)>,
+   input: String,
+   history: Vec<String>,
+   history_pos: Option<usize>,
+   sink: LogSink,
+}
+
+impl Console {
+   pub(crate) fn new(sink: LogSink) -> Self {
+      Self {
+         layout: ConsoleLayout::Split,
+         min_level: MsgType::Trace,
+         search: String::new(),
+         mode: ConsoleMode::Browse,
+         scroll: 0,
+         view: Cell::new((0, 0)),
+         input: String::new(),
+         history: Vec::new(),
+         history_pos: None,
+         sink,
+      }
+   }
+
+   pub fn layout(&self) -> ConsoleLayout {
+      self.layout
+   }
+   pub fn set_layout(&mut self, layout: ConsoleLayout) {
+      self.layout = layout;
+   }
+   pub fn min_level(&self) -> MsgType {
+      self.min_level
+   }
+   pub fn set_min_level(&mut self, level: MsgType) {
+      self.min_level = level;
+      self.scroll = 0;
+   }
+   pub fn search(&self) -> &str {
+      &self.search
+   }
+   pub fn set_search(&mut self, search: &str) {
+      self.search = search.to_string();
+      self.scroll = 0;
+   }
+
+   fn max_scroll(&self) -> usize {
+      let (total, height) = self.view.get();
+      total.saturating_sub(height)
+   }
+   fn scroll_by(&mut self, rows: isize) {
+      let scroll = self.scroll as isize + rows;
+      self.scroll = (scroll.max(0) as usize).min(self.max_scroll());
+   }
+
+   pub fn history(&self) -> &[String] {
+      &self.history
+   }
+
+   pub(crate) fn handle(&mut self, event: &Event, cfg: &Cfg) -> ConsoleInput {
+      match event {
+         Event::Key(key) if key.kind == KeyEventKind::Press => self.handle_key(key, cfg),
+         Event::Key(_) => ConsoleInput::Handled,
+         // the console only scrolls, clicks still go to the app
+         Event::Mouse(mouse) => match mouse.kind {
+            MouseEventKind::ScrollUp => {
+               self.scroll_by(3);
+               ConsoleInput::Handled
+            }
+            MouseEventKind::ScrollDown => {
+               self.scroll_by(-3);
+               ConsoleInput::Handled
+            }
+            _ => ConsoleInput::Ignored,
+         },
+         _ => ConsoleInput::Ignored,
+      }
+   }
+
+   fn handle_key(&mut self, key: &KeyEvent, cfg: &Cfg) -> ConsoleInput {
+      // chords and function keys go to the app, that's where ctrl-c and the console
+      // toggle are bound
+      let chord = key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+      if chord || matches!(key.code, KeyCode::F(_)) {
+         return ConsoleInput::Ignored;
+      }
+      if self.mode == ConsoleMode::Repl {
+         match key.code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+               self.input.pop();
+            }
+            KeyCode::Enter => self.eval(cfg),
+            KeyCode::Tab => self.complete(cfg),
+            KeyCode::Up => self.history_step(-1),
+            KeyCode::Down => self.history_step(1),
+            KeyCode::Esc => {
+               self.input.clear();
+               self.history_pos = None;
+               self.mode = ConsoleMode::Browse;
+            }
+            _ => {}
+         }
+         return ConsoleInput::Handled;
+      }
+      if self.mode == ConsoleMode::Search {
+         match key.code {
+            KeyCode::Char(c) => {
+               self.search.push(c);
+               self.scroll = 0;
+            }
+            KeyCode::Backspace => {
+               self.search.pop();
+            }
+            KeyCode::Enter => self.mode = ConsoleMode::Browse,
+            KeyCode::Esc => {
+               self.search.clear();
+               self.mode = ConsoleMode::Browse;
+            }
+            _ => {}
+         }
+         return ConsoleInput::Handled;
+      }
+
+      let page = self.view.get().1.max(1) as isize;
+      match key.code {
+         KeyCode::Up | KeyCode::Char('k') => self.scroll_by(1),
+         KeyCode::Down | KeyCode::Char('j') => self.scroll_by(-1),
+         KeyCode::PageUp => self.scroll_by(page),
+         KeyCode::PageDown => self.scroll_by(-page),
+         KeyCode::Home | KeyCode::Char('g') => self.scroll = self.max_scroll(),
+         KeyCode::End | KeyCode::Char('G') => self.scroll = 0,
+         KeyCode::Char('/') => self.mode = ConsoleMode::Search,
+         KeyCode::Char(':') => self.mode = ConsoleMode::Repl,
+         KeyCode::Char('f') => {
+            let i = LEVELS.iter().position(|l| *l == self.min_level).unwrap_or(0);
+            self.set_min_level(LEVELS[(i + 1) % LEVELS.len()]);
+         }
+         KeyCode::Char('l') => {
+            self.layout = match self.layout {
+               ConsoleLayout::Full => ConsoleLayout::Split,
+               ConsoleLayout::Split => ConsoleLayout::Full,
+            }
+         }
+         KeyCode::Esc if !self.search.is_empty() => self.search.clear(),
+         KeyCode::Esc => return ConsoleInput::Close,
+         _ => return ConsoleInput::Ignored,
+      }
+      ConsoleInput::Handled
+   }
+
+   fn history_step(&mut self, step: isize) {
+      if self.history.is_empty() {
+         return;
+      }
+      let last = self.history.len() as isize - 1;
+      let pos = match self.history_pos {
+         Some(p) => p as isize + step,
+         None if step < 0 => last,
+         None => return,
+      };
+      if pos > last {
+         self.history_pos = None;
+         self.input.clear();
+         return;
+      }
+      let pos = pos.max(0) as usize;
+      self.history_pos = Some(pos);
+      self.input = self.history[pos].clone();
+   }
+
+   fn log(&self, msg: &str, typ: MsgType) {
+      self.sink.push(msg, typ, LogSource::Lua);
+   }
+
+   // tries the line as an expression first so `cfg.theme` prints without a `return`
+   fn eval(&mut self, cfg: &Cfg) {
+      let line = std::mem::take(&mut self.input);
+      self.history_pos = None;
+      if line.trim().is_empty() {
+         return;
+      }
+      if self.history.last() != Some(&line) {
+         self.history.push(line.clone());
+      }
+      self.log(&format!("> {line}"), MsgType::Debug);
+
+      let lua = match cfg {
+         Some(lua) => lua,
+         None => return self.log("no lua state to evaluate in", MsgType::Warn),
+      };
+      let func = match lua.load(format!("return {line}")).into_function() {
+         Ok(f) => Ok(f),
+         Err(_) => lua.load(&line).into_function(),
+      };
+      match func.and_then(|f| f.call::<LuaMultiValue>(())) {
+         Ok(values) => {
+            let out: Vec<String> = values.into_iter().map(|v| repr(&v, 1)).collect();
+            if !out.is_empty() {
+               self.log(&out.join("\t"), MsgType::Info);
+            }
+         }
+         Err(e) => self.log(&e.to_string(), MsgType::Error),
+      }
+   }
+
+   fn complete(&mut self, cfg: &Cfg) {
+      let lua = match cfg {
+         Some(lua) => lua,
+         None => return,
+      };
+      let word_start = self
+         .input
+         .char_indices()
+         .rev()
+         .find(|(_, c)| !(c.is_alphanumeric() || *c == '_' || *c == '.' || *c == ':'))
+         .map_or(0, |(i, c)| i + c.len_utf8());
+      let word = &self.input[word_start..];
+      let (path, prefix) = match word.rfind(['.', ':']) {
+         Some(i) => (&word[..i], &word[i + 1..]),
+         None => ("", word),
+      };
+
+      let mut table = lua.globals();
+      for key in path.split(['.', ':']).filter(|k| !k.is_empty()) {
+         table = match table.get::<LuaValue>(key) {
+            Ok(LuaValue::Table(t)) => t,
+            _ => return,
+         };
+      }
+      let mut names = table_keys(&table)
+         .into_iter()
+         .filter(|k| k.starts_with(prefix))
+         .collect::<Vec<_>>();
+      names.sort();
+
+      let prefix_len = prefix.len();
+      let common = match names.first() {
+         Some(first) => names.iter().fold(first.clone(), |acc, name| {
+            acc.chars()
+               .zip(name.chars())
+               .take_while(|(a, b)| a == b)
+               .map(|(a, _)| a)
+               .collect()
+         }),
+         None => return,
+      };
+      self.input.push_str(&common[prefix_len..]);
+      if names.len() > 1 {
+         self.log(&names.join("  "), MsgType::Debug);
+      }
+   }
+
+   fn rows(&self, debug: &Debug, width: usize) -> Vec<Line<'static>> {
+      let needle = self.search.to_lowercase();
+      let mut rows = Vec::new();
+      for entry in debug.filter(self.min_level) {
+         if !needle.is_empty() && !entry.msg.msg().to_lowercase().contains(&needle) {
+            continue;
+         }
+         let typ = *entry.msg.msg_type();
+         let prefix = format!(
+            "{:>9.3} t{:<6} {:<5} {:<4} ",
+            entry.at.as_secs_f32(),
+            entry.tick,
+            typ.name(),
+            entry.source.name()
+         );
+         let prefix_width = prefix.width();
+         let style = Style::default().fg(typ.color());
+         let chunks = wrap(entry.msg.msg(), width.saturating_sub(prefix_width).max(1));
+         for (i, chunk) in chunks.into_iter().enumerate() {
+            let head = match i {
+               0 => prefix.clone(),
+               _ => " ".repeat(prefix_width),
+            };
+            rows.push(Line::from(vec![Span::styled(head, style), Span::raw(chunk)]));
+         }
+      }
+      rows
+   }
+
+   pub(crate) fn prompt(&self) -> Line<'static> {
+      match (self.mode, self.search.is_empty()) {
+         (ConsoleMode::Repl, _) => Line::from(format!("> {}_", self.input)),
+         (ConsoleMode::Search, _) => Line::from(format!("/{}_", self.search)),
+         (ConsoleMode::Browse, false) => Line::from(format!("/{}  [esc] clear", self.search)),
+         (ConsoleMode::Browse, true) => Line::from(
+            "[up/down] scroll  [/] search  [:] lua  [f] level  [l] layout  [esc] close",
+         )
+         .style(Style::default().fg(Color::DarkGray)),
+      }
+   }
+
+   pub(crate) fn render(&self, debug: &Debug, area: Rect, buf: &mut Buffer) {
+      let area = match self.layout {
+         ConsoleLayout::Full => area,
+         ConsoleLayout::Split => {
+            let height = area.height / 2;
+            Rect::new(area.x, area.y + area.height - height, area.width, height)
+         }
+      };
+      let title = format!(
+         " console [{}+] {}/{} ",
+         self.min_level.name(),
+         debug.filter(self.min_level).count(),
+         debug.len()
+      );
+      let block = Block::bordered()
+         .title(title)
+         .border_style(Style::default().fg(Color::Magenta));
+      let inner = block.inner(area);
+      Clear.render(area, buf);
+      block.render(area, buf);
+      if inner.height == 0 {
+         return;
+      }
+
+      let list_area = Rect::new(inner.x, inner.y, inner.width, inner.height - 1);
+      let prompt_area = Rect::new(inner.x, inner.y + inner.height - 1, inner.width, 1);
+
+      let rows = self.rows(debug, list_area.width as usize);
+      let height = list_area.height as usize;
+      self.view.set((rows.len(), height));
+      let scroll = self.scroll.min(rows.len().saturating_sub(height));
+      let end = rows.len() - scroll;
+      let start = end.saturating_sub(height);
+      let visible: Vec<Line> = rows.into_iter().skip(start).take(end - start).collect();
+
+      Paragraph::new(visible).render(list_area, buf);
+      Paragraph::new(self.prompt()).render(prompt_area, buf);
+   }
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+   let mut out = Vec::new();
+   for line in text.split('\n') {
+      let mut current = String::new();
+      let mut current_width = 0;
+      for ch in line.chars() {
+         let ch_width = ch.width().unwrap_or(0);
+         if current_width + ch_width > width && !current.is_empty() {
+            out.push(std::mem::take(&mut current));
+            current_width = 0;
+         }
+         current.push(ch);
+         current_width += ch_width;
+      }
+      out.push(current);
+   }
+   out
+}
+
+fn table_keys(table: &LuaTable) -> Vec<String> {
+   table
+      .pairs::<LuaValue, LuaValue>()
+      .filter_map(|pair| match pair {
+         Ok((LuaValue::String(k), _)) => Some(k.to_string_lossy()),
+         _ => None,
+      })
+      .collect()
+}
+
+fn repr(value: &LuaValue, depth: usize) -> String {
+   match value {
+      LuaValue::String(s) => format!("{:?}", s.to_string_lossy()),
+      LuaValue::Table(t) if depth > 0 => {
+         let mut items = Vec::new();
+         for (k, v) in t.pairs::<LuaValue, LuaValue>().flatten().take(16) {
+            let key = match &k {
+               LuaValue::String(s) => s.to_string_lossy(),
+               other => format!("[{}]", repr(other, 0)),
+            };
+            items.push(format!("{key} = {}", repr(&v, depth - 1)));
+         }
+         format!("{{ {} }}", items.join(", "))
+      }
+      other => other.to_string().unwrap_or_else(|_| other.type_name().to_string()),
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+      Event::Key(KeyEvent::new(code, modifiers))
+   }
+   fn press(console: &mut Console, code: KeyCode) -> ConsoleInput {
+      console.handle(&key(code, KeyModifiers::NONE), &None)
+   }
+
+   #[test]
+   fn chords_and_unbound_keys_fall_through() {
+      let mut console = Console::new(LogSink::default());
+      let ctrl_c = key(KeyCode::Char('c'), KeyModifiers::CONTROL);
+      assert_eq!(console.handle(&ctrl_c, &None), ConsoleInput::Ignored);
+      assert_eq!(press(&mut console, KeyCode::F(12)), ConsoleInput::Ignored);
+      assert_eq!(press(&mut console, KeyCode::Char('`')), ConsoleInput::Ignored);
+      assert_eq!(press(&mut console, KeyCode::Char('j')), ConsoleInput::Handled);
+   }
+
+   #[test]
+   fn typing_keeps_chars_but_not_chords() {
+      let mut console = Console::new(LogSink::default());
+      press(&mut console, KeyCode::Char(':'));
+      assert_eq!(press(&mut console, KeyCode::Char('`')), ConsoleInput::Handled);
+      assert_eq!(console.input, "`");
+      let ctrl_c = key(KeyCode::Char('c'), KeyModifiers::CONTROL);
+      assert_eq!(console.handle(&ctrl_c, &None), ConsoleInput::Ignored);
+      assert_eq!(press(&mut console, KeyCode::Esc), ConsoleInput::Handled);
+      assert_eq!(press(&mut console, KeyCode::Esc), ConsoleInput::Close);
+   }
+
+   #[test]
+   fn only_scrolling_stays_in_the_console() {
+      use ratatui::crossterm::event::{MouseButton, MouseEvent};
+      let mut console = Console::new(LogSink::default());
+      let mouse = |kind| {
+         let event = MouseEvent { kind, column: 0, row: 0, modifiers: KeyModifiers::NONE };
+         Event::Mouse(event)
+      };
+      let scroll = mouse(MouseEventKind::ScrollUp);
+      assert_eq!(console.handle(&scroll, &None), ConsoleInput::Handled);
+      let click = mouse(MouseEventKind::Down(MouseButton::Left));
+      assert_eq!(console.handle(&click, &None), ConsoleInput::Ignored);
+   }
+
+   // a console in repl mode with its own lua state, and what it logged so far
+   fn repl(src: &str) -> (Console, LogSink, Cfg) {
+      let sink = LogSink::default();
+      let mut console = Console::new(sink.clone());
+      console.mode = ConsoleMode::Repl;
+      let lua = mlua::Lua::new();
+      lua.load(src).exec().unwrap();
+      (console, sink, Some(lua))
+   }
+   fn enter(console: &mut Console, cfg: &Cfg, line: &str) {
+      console.input = line.to_string();
+      console.handle(&key(KeyCode::Enter, KeyModifiers::NONE), cfg);
+   }
+   fn logged(sink: &LogSink) -> Vec<(String, MsgType)> {
+      sink.drain().into_iter().map(|(m, _)| (m.msg().to_string(), *m.msg_type())).collect()
+   }
+
+   #[test]
+   fn repl_prints_expressions_and_runs_statements() {
+      let (mut console, sink, cfg) = repl("x = 2");
+      enter(&mut console, &cfg, "x * 21");
+      enter(&mut console, &cfg, "x = 5");
+      enter(&mut console, &cfg, "x, 'a'");
+      enter(&mut console, &cfg, "nope()");
+      let out = logged(&sink);
+      assert_eq!(out[0], ("> x * 21".to_string(), MsgType::Debug));
+      assert_eq!(out[1], ("42".to_string(), MsgType::Info));
+      assert_eq!(out[2].0, "> x = 5");
+      assert_eq!(out[4], ("5\t\"a\"".to_string(), MsgType::Info));
+      assert_eq!(out.last().unwrap().1, MsgType::Error);
+
+      let sink = LogSink::default();
+      let mut console = Console::new(sink.clone());
+      console.mode = ConsoleMode::Repl;
+      enter(&mut console, &None, "1");
+      assert_eq!(logged(&sink)[1].1, MsgType::Warn);
+   }
+
+   #[test]
+   fn history_walks_back_and_forward() {
+      let (mut console, _sink, cfg) = repl("");
+      for line in ["a = 1", "b = 2", "b = 2", "  "] {
+         enter(&mut console, &cfg, line);
+      }
+      assert_eq!(console.history(), ["a = 1", "b = 2"]);
+      press(&mut console, KeyCode::Up);
+      assert_eq!(console.input, "b = 2");
+      press(&mut console, KeyCode::Up);
+      press(&mut console, KeyCode::Up);
+      assert_eq!(console.input, "a = 1");
+      press(&mut console, KeyCode::Down);
+      assert_eq!(console.input, "b = 2");
+      press(&mut console, KeyCode::Down);
+      assert_eq!(console.input, "");
+      press(&mut console, KeyCode::Down);
+      assert_eq!(console.input, "");
+   }
+
+   #[test]
+   fn tab_completes_global_and_nested_names() {
+      let (mut console, sink, cfg) = repl("player_name = 1\nplayer_hp = 2\ntheme = { accent = 1 }");
+      let tab = key(KeyCode::Tab, KeyModifiers::NONE);
+      console.input = "print(theme.ac".to_string();
+      console.handle(&tab, &cfg);
+      assert_eq!(console.input, "print(theme.accent");
+      sink.drain();
+
+      console.input = "pla".to_string();
+      console.handle(&tab, &cfg);
+      assert_eq!(console.input, "player_");
+      assert_eq!(logged(&sink), vec![("player_hp  player_name".to_string(), MsgType::Debug)]);
+      console.input = "zzz".to_string();
+      console.handle(&tab, &cfg);
+      assert_eq!(console.input, "zzz");
+   }
+}
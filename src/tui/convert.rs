@@ -0,0 +1,208 @@
+use crate::{LuaError, LuaResult, LuaTable, LuaValue};
+use kolor::RGB;
+use mlua::{FromLua, IntoLua, Lua};
+use ratatui::prelude::{Color, Modifier};
+use ratatui::style::Style;
+use std::str::FromStr;
+
+const STYLE_MODIFIERS: [(&str, Modifier); 7] = [
+   ("bold", Modifier::BOLD),
+   ("italic", Modifier::ITALIC),
+   ("underline", Modifier::UNDERLINED),
+   ("strike", Modifier::CROSSED_OUT),
+   ("dim", Modifier::DIM),
+   ("reverse", Modifier::REVERSED),
+   ("blink", Modifier::SLOW_BLINK),
+];
+
+// `#rgb`, `#rrggbb`, the leading `#` is optional
+pub fn parse_hex_rgb(hex: &str) -> Result<(u8, u8, u8), String> {
+   let digits = hex.strip_prefix('#').unwrap_or(hex);
+   // from_str_radix alone would take a leading sign
+   if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+      return Err(format!("invalid hex color {hex:?}: not a hex number"));
+   }
+   let full: String = match digits.len() {
+      3 => digits.chars().flat_map(|c| [c, c]).collect(),
+      6 => digits.to_string(),
+      n => return Err(format!("invalid hex color {hex:?}: expected 3 or 6 digits, got {n}")),
+   };
+   let v = u32::from_str_radix(&full, 16).map_err(|e| format!("invalid hex color {hex:?}: {e}"))?;
+   Ok(((v >> 16) as u8, (v >> 8) as u8, v as u8))
+}
+
+pub fn parse_hex(hex: &str) -> Result<RGB, String> {
+   let (r, g, b) = parse_hex_rgb(hex)?;
+   Ok(RGB::from_hex(&format!("#{r:02x}{g:02x}{b:02x}")))
+}
+
+pub fn parse_hex_color(hex: &str) -> Result<Color, String> {
+   let (r, g, b) = parse_hex_rgb(hex)?;
+   Ok(Color::Rgb(r, g, b))
+}
+
+// hex strings, anything ratatui knows by name ("red", "light-blue") or a 256 palette index
+pub fn parse_color(value: &str) -> Result<Color, String> {
+   if value.starts_with('#') {
+      return parse_hex_color(value);
+   }
+   Color::from_str(value).map_err(|_| format!("unknown color {value:?}"))
+}
+
+fn conversion_err(from: &LuaValue, to: &str, msg: String) -> LuaError {
+   LuaError::FromLuaConversionError {
+      from: from.type_name(),
+      to: to.to_string(),
+      message: Some(msg),
+   }
+}
+
+#[derive(Debug, Clone)]
+pub struct LuaRGB(pub RGB);
+
+impl FromLua for LuaRGB {
+   fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+      match &value {
+         LuaValue::String(s) => parse_hex(&s.to_string_lossy())
+            .map(LuaRGB)
+            .map_err(|e| conversion_err(&value, "RGB", e)),
+         _ => Err(conversion_err(&value, "RGB", "expected a hex string".to_string())),
+      }
+   }
+}
+
+impl IntoLua for LuaRGB {
+   fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+      let hex = format!(
+         "#{:02x}{:02x}{:02x}",
+         (self.0.r() * 255.0) as u8,
+         (self.0.g() * 255.0) as u8,
+         (self.0.b() * 255.0) as u8
+      );
+      hex.into_lua(lua)
+   }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LuaColor(pub Color);
+
+impl FromLua for LuaColor {
+   fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+      match &value {
+         LuaValue::String(s) => parse_color(&s.to_string_lossy())
+            .map(LuaColor)
+            .map_err(|e| conversion_err(&value, "Color", e)),
+         LuaValue::Integer(i) if (0..=255).contains(i) => Ok(LuaColor(Color::Indexed(*i as u8))),
+         _ => Err(conversion_err(
+            &value,
+            "Color",
+            "expected a color name, hex string or palette index".to_string(),
+         )),
+      }
+   }
+}
+
+impl IntoLua for LuaColor {
+   fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+      match self.0 {
+         Color::Rgb(r, g, b) => format!("#{r:02x}{g:02x}{b:02x}").into_lua(lua),
+         Color::Indexed(i) => (i as i64).into_lua(lua),
+         c => c.to_string().to_lowercase().into_lua(lua),
+      }
+   }
+}
+
+// `{ fg = "#aabbcc", bg = "red", ul = "blue", bold = true, italic = true }`, a plain string sets fg
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LuaStyle(pub Style);
+
+impl FromLua for LuaStyle {
+   fn from_lua(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+      let table = match value {
+         LuaValue::Table(t) => t,
+         LuaValue::Nil => return Ok(LuaStyle::default()),
+         LuaValue::String(_) => {
+            let LuaColor(fg) = LuaColor::from_lua(value, lua)?;
+            return Ok(LuaStyle(Style::default().fg(fg)));
+         }
+         other => {
+            return Err(conversion_err(
+               &other,
+               "Style",
+               "expected a table or a color".to_string(),
+            ))
+         }
+      };
+      let mut style = Style::default();
+      for pair in table.pairs::<String, LuaValue>() {
+         let (key, value) = pair?;
+         let color = |value: LuaValue| {
+            LuaColor::from_lua(value, lua)
+               .map(|c| c.0)
+               .map_err(|e| LuaError::runtime(format!("style.{key}: {e}")))
+         };
+         style = match key.as_str() {
+            "fg" => style.fg(color(value)?),
+            "bg" => style.bg(color(value)?),
+            "ul" => style.underline_color(color(value)?),
+            name => match STYLE_MODIFIERS.iter().find(|(n, _)| *n == name) {
+               Some((_, m)) if bool::from_lua(value, lua)? => style.add_modifier(*m),
+               Some((_, m)) => style.remove_modifier(*m),
+               None => return Err(LuaError::runtime(format!("unknown style key {name:?}"))),
+            },
+         };
+      }
+      Ok(LuaStyle(style))
+   }
+}
+
+impl IntoLua for LuaStyle {
+   fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+      let table: LuaTable = lua.create_table()?;
+      if let Some(fg) = self.0.fg {
+         table.set("fg", LuaColor(fg))?;
+      }
+      if let Some(bg) = self.0.bg {
+         table.set("bg", LuaColor(bg))?;
+      }
+      if let Some(ul) = self.0.underline_color {
+         table.set("ul", LuaColor(ul))?;
+      }
+      for (name, modifier) in STYLE_MODIFIERS {
+         if self.0.add_modifier.contains(modifier) {
+            table.set(name, true)?;
+         }
+      }
+      Ok(LuaValue::Table(table))
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn hex_accepts_short_and_long_forms() {
+      assert_eq!(parse_hex_rgb("#ff8000"), Ok((255, 128, 0)));
+      assert_eq!(parse_hex_rgb("f80"), Ok((255, 136, 0)));
+      assert_eq!(parse_hex_color("#ABC"), Ok(Color::Rgb(0xaa, 0xbb, 0xcc)));
+   }
+
+   #[test]
+   fn hex_rejects_anything_else() {
+      assert!(parse_hex_rgb("#+ffff").is_err());
+      assert!(parse_hex_rgb("#-ff").is_err());
+      assert!(parse_hex_rgb("#ggg").is_err());
+      assert!(parse_hex_rgb("#ffff").is_err());
+      assert!(parse_hex_rgb("#ééé").is_err());
+   }
+
+   #[test]
+   fn colors_by_hex_name_or_index() {
+      assert_eq!(parse_color("#0f0"), Ok(Color::Rgb(0, 255, 0)));
+      assert_eq!(parse_color("red"), Ok(Color::Red));
+      assert_eq!(parse_color("42"), Ok(Color::Indexed(42)));
+      assert!(parse_color("#12").is_err());
+      assert!(parse_color("nope").is_err());
+   }
+}
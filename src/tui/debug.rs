@@ -1,13 +1,21 @@
+use crate::tui::LogFile;
+use crate::{Console, DebugBar, Profiler};
 use ratatui::prelude::Color;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Msg {
    msg: String,
    typ: MsgType,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum MsgType {
+   Trace,
+   Debug,
    Info,
    Event,
    Warn,
@@ -17,12 +25,35 @@ pub enum MsgType {
 impl MsgType {
    pub fn color(&self) -> Color {
       match self {
+         MsgType::Trace => Color::DarkGray,
+         MsgType::Debug => Color::Cyan,
          MsgType::Info => Color::Blue,
          MsgType::Event => Color::Green,
          MsgType::Warn => Color::Yellow,
          MsgType::Error => Color::Red,
       }
    }
+   pub fn name(&self) -> &'static str {
+      match self {
+         MsgType::Trace => "trace",
+         MsgType::Debug => "debug",
+         MsgType::Info => "info",
+         MsgType::Event => "event",
+         MsgType::Warn => "warn",
+         MsgType::Error => "error",
+      }
+   }
+   pub fn from_name(name: &str) -> Option<Self> {
+      match name.to_lowercase().as_str() {
+         "trace" => Some(MsgType::Trace),
+         "debug" => Some(MsgType::Debug),
+         "info" => Some(MsgType::Info),
+         "event" => Some(MsgType::Event),
+         "warn" | "warning" => Some(MsgType::Warn),
+         "error" => Some(MsgType::Error),
+         _ => None,
+      }
+   }
 }
 
 impl Msg {
@@ -64,17 +95,281 @@ impl Msg {
    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogSource {
+   Rust,
+   Lua,
+}
+
+impl LogSource {
+   pub fn name(&self) -> &'static str {
+      match self {
+         LogSource::Rust => "rust",
+         LogSource::Lua => "lua",
+      }
+   }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+   pub msg: Msg,
+   pub source: LogSource,
+   // time since the tui started
+   pub at: Duration,
+   pub tick: u32,
+   pub frame: u32,
+}
+
+// thread safe inbox for logs coming from outside the tui (lua, other threads),
+// drained into the ring buffer every tick
+#[derive(Debug, Clone, Default)]
+pub struct LogSink {
+   pending: Arc<Mutex<Vec<(Msg, LogSource)>>>,
+}
+
+impl LogSink {
+   pub fn push(&self, msg: &str, typ: MsgType, source: LogSource) {
+      if let Ok(mut pending) = self.pending.lock() {
+         pending.push((Msg::new(msg, typ), source));
+      }
+   }
+   pub(crate) fn drain(&self) -> Vec<(Msg, LogSource)> {
+      match self.pending.lock() {
+         Ok(mut pending) => pending.drain(..).collect(),
+         Err(_) => Vec::new(),
+      }
+   }
+}
+
+// the single message `Debug` used to hold. setting it logs the message like `Debug::info`
+// and friends do, it only remembers the last one it was given
+#[derive(Debug, Clone)]
+pub struct CurrentLog {
+   last: Msg,
+   sink: LogSink,
+}
+
+impl CurrentLog {
+   pub fn clear(&mut self) {
+      self.last.clear();
+   }
+   pub fn msg(&self) -> &str {
+      self.last.msg()
+   }
+   pub fn msg_type(&self) -> &MsgType {
+      self.last.msg_type()
+   }
+   pub fn set_msg(&mut self, msg: &str, typ: MsgType) {
+      self.last.set_msg(msg, typ);
+      self.sink.push(msg, typ, LogSource::Rust);
+   }
+   pub fn set_info_msg(&mut self, msg: &str) {
+      self.set_msg(msg, MsgType::Info);
+   }
+   pub fn set_warn_msg(&mut self, msg: &str) {
+      self.set_msg(msg, MsgType::Warn);
+   }
+   pub fn set_error_msg(&mut self, msg: &str) {
+      self.set_msg(msg, MsgType::Error);
+   }
+   pub fn set_event_msg(&mut self, msg: &str) {
+      self.set_msg(msg, MsgType::Event);
+   }
+}
+
 #[derive(Debug)]
 pub struct Debug {
-   pub current_log: Msg,
+   #[deprecated(note = "log with Debug::info/warn/error/event and read Debug::current_log()")]
+   pub current_log: CurrentLog,
+   pub bar: DebugBar,
+   pub console: Console,
+   pub profiler: Profiler,
    pub(crate) current_fn: Msg,
+   logs: VecDeque<LogEntry>,
+   capacity: usize,
+   expiry: Duration,
+   sink: LogSink,
+   file: Option<LogFile>,
+   start: Instant,
+   tick: u32,
+   frame: u32,
 }
 
 impl Debug {
+   #[allow(deprecated)]
    pub(crate) fn new() -> Self {
+      let sink = LogSink::default();
       Self {
-         current_log: Msg::new("???", MsgType::Info),
+         current_log: CurrentLog {
+            last: Msg::new("???", MsgType::Info),
+            sink: sink.clone(),
+         },
+         bar: DebugBar::default(),
+         console: Console::new(sink.clone()),
+         profiler: Profiler::default(),
          current_fn: Msg::new("???", MsgType::Info),
+         logs: VecDeque::new(),
+         capacity: 512,
+         expiry: Duration::from_secs(3),
+         sink,
+         file: None,
+         start: Instant::now(),
+         tick: 0,
+         frame: 0,
       }
    }
+
+   pub fn push(&mut self, msg: &str, typ: MsgType, source: LogSource) {
+      self.push_msg(Msg::new(msg, typ), source);
+   }
+   fn push_msg(&mut self, msg: Msg, source: LogSource) {
+      while self.logs.len() >= self.capacity.max(1) {
+         self.logs.pop_front();
+      }
+      let entry = LogEntry {
+         msg,
+         source,
+         at: self.start.elapsed(),
+         tick: self.tick,
+         frame: self.frame,
+      };
+      if let Some(file) = &mut self.file {
+         file.write(&entry);
+      }
+      self.logs.push_back(entry);
+   }
+
+   pub fn trace(&mut self, msg: &str) {
+      self.push(msg, MsgType::Trace, LogSource::Rust);
+   }
+   pub fn debug(&mut self, msg: &str) {
+      self.push(msg, MsgType::Debug, LogSource::Rust);
+   }
+   pub fn info(&mut self, msg: &str) {
+      self.push(msg, MsgType::Info, LogSource::Rust);
+   }
+   pub fn event(&mut self, msg: &str) {
+      self.push(msg, MsgType::Event, LogSource::Rust);
+   }
+   pub fn warn(&mut self, msg: &str) {
+      self.push(msg, MsgType::Warn, LogSource::Rust);
+   }
+   pub fn error(&mut self, msg: &str) {
+      self.push(msg, MsgType::Error, LogSource::Rust);
+   }
+
+   pub fn logs(&self) -> impl DoubleEndedIterator<Item = &LogEntry> {
+      self.logs.iter()
+   }
+   pub fn filter(&self, min: MsgType) -> impl DoubleEndedIterator<Item = &LogEntry> {
+      self.logs.iter().filter(move |l| *l.msg.msg_type() >= min)
+   }
+   pub fn latest(&self) -> Option<&LogEntry> {
+      self.logs.back()
+   }
+   // the latest entry, until it is older than the expiry
+   pub fn current_log(&self) -> Option<&LogEntry> {
+      self
+         .latest()
+         .filter(|l| self.start.elapsed().saturating_sub(l.at) < self.expiry)
+   }
+   pub fn clear(&mut self) {
+      self.logs.clear();
+   }
+   pub fn len(&self) -> usize {
+      self.logs.len()
+   }
+   pub fn is_empty(&self) -> bool {
+      self.logs.is_empty()
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.capacity
+   }
+   pub fn set_capacity(&mut self, capacity: usize) {
+      self.capacity = capacity;
+      while self.logs.len() > self.capacity {
+         self.logs.pop_front();
+      }
+   }
+   pub fn expiry(&self) -> Duration {
+      self.expiry
+   }
+   pub fn set_expiry(&mut self, expiry: Duration) {
+      self.expiry = expiry;
+   }
+   pub fn sink(&self) -> LogSink {
+      self.sink.clone()
+   }
+
+   pub(crate) fn log_to(&mut self, path: PathBuf) {
+      self.file = LogFile::open(path);
+   }
+
+   pub(crate) fn stamp(&mut self, tick: u32, frame: u32) {
+      self.tick = tick;
+      self.frame = frame;
+   }
+   pub(crate) fn flush_sink(&mut self) {
+      for (msg, source) in self.sink.drain() {
+         self.push_msg(msg, source);
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn msgs<'a>(logs: impl Iterator<Item = &'a LogEntry>) -> Vec<&'a str> {
+      logs.map(|l| l.msg.msg()).collect()
+   }
+
+   #[test]
+   fn the_oldest_entries_go_first() {
+      let mut debug = Debug::new();
+      debug.set_capacity(3);
+      for n in ["a", "b", "c", "d"] {
+         debug.info(n);
+      }
+      assert_eq!(msgs(debug.logs()), vec!["b", "c", "d"]);
+      debug.set_capacity(1);
+      assert_eq!(msgs(debug.logs()), vec!["d"]);
+      assert_eq!(debug.latest().unwrap().msg.msg(), "d");
+   }
+
+   #[test]
+   fn filter_keeps_the_level_and_above() {
+      let mut debug = Debug::new();
+      debug.trace("t");
+      debug.info("i");
+      debug.warn("w");
+      debug.error("e");
+      assert_eq!(msgs(debug.filter(MsgType::Warn)), vec!["w", "e"]);
+      assert_eq!(msgs(debug.filter(MsgType::Trace)).len(), 4);
+   }
+
+   #[test]
+   fn current_log_expires() {
+      let mut debug = Debug::new();
+      assert!(debug.current_log().is_none());
+      debug.event("now");
+      assert_eq!(debug.current_log().unwrap().msg.msg(), "now");
+      debug.set_expiry(Duration::ZERO);
+      assert!(debug.current_log().is_none());
+      assert!(debug.latest().is_some());
+   }
+
+   #[test]
+   #[allow(deprecated)]
+   fn the_old_field_and_the_sink_log_on_flush() {
+      let mut debug = Debug::new();
+      debug.current_log.set_error_msg("old");
+      debug.sink().push("other thread", MsgType::Info, LogSource::Lua);
+      assert_eq!(debug.current_log.msg(), "old");
+      assert!(debug.is_empty());
+      debug.flush_sink();
+      assert_eq!(msgs(debug.logs()), vec!["old", "other thread"]);
+      assert_eq!(*debug.filter(MsgType::Error).next().unwrap().msg.msg_type(), MsgType::Error);
+   }
 }
@@ -0,0 +1,398 @@
+use crate::{
+   App, Migration, Nav, Screen, ScreenStack, TaskCtx, TaskEvent, TaskId, TUIMutRef, TUIRef,
+   Transition,
+};
+use ratatui::crossterm::event::Event;
+use ratatui::prelude::Buffer;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// messages an update can queue in one tick before the rest is dropped, guards against
+// updates that keep answering themselves
+const MAX_MESSAGES_PER_TICK: usize = 1024;
+
+type TaskFn<M> = Box<dyn FnOnce(&TaskCtx) -> M + Send>;
+
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(0);
+
+// names a repeating timer so a later update can stop it with `ElmCommand::Cancel`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerHandle(u64);
+
+impl TimerHandle {
+   fn next() -> Self {
+      TimerHandle(NEXT_HANDLE.fetch_add(1, Ordering::Relaxed))
+   }
+   fn timer_id(&self) -> String {
+      format!("elm:every:{}", self.0)
+   }
+}
+
+// side effects an update asks the runtime for. `S` is the state the app's screens share,
+// the app itself for an `ElmApp`
+pub enum ElmCommand<M, S = ()> {
+   None,
+   Batch(Vec<ElmCommand<M, S>>),
+   // handled later in the same tick
+   Message(M),
+   After(Duration, M),
+   Every(TimerHandle, Duration, fn() -> M),
+   Cancel(TimerHandle),
+   // runs on the task pool, the returned message comes back on a later tick
+   Task(String, TaskFn<M>),
+   // screen stack changes, applied in order after the update that returned them
+   Nav(Nav<S>),
+   Reload,
+   Exit,
+}
+
+impl<M, S> ElmCommand<M, S> {
+   pub fn none() -> Self {
+      ElmCommand::None
+   }
+   pub fn batch(cmds: impl IntoIterator<Item = ElmCommand<M, S>>) -> Self {
+      ElmCommand::Batch(cmds.into_iter().collect())
+   }
+   pub fn task(name: &str, f: impl FnOnce(&TaskCtx) -> M + Send + 'static) -> Self {
+      ElmCommand::Task(name.to_string(), Box::new(f))
+   }
+   // keep the handle in the model to cancel the timer later
+   pub fn every(interval: Duration, f: fn() -> M) -> (Self, TimerHandle) {
+      let handle = TimerHandle::next();
+      (ElmCommand::Every(handle, interval, f), handle)
+   }
+
+   fn nav(f: impl FnOnce(&mut Nav<S>)) -> Self {
+      let mut nav = Nav::new();
+      f(&mut nav);
+      ElmCommand::Nav(nav)
+   }
+   pub fn push(screen: impl Screen<S> + 'static) -> Self {
+      Self::nav(|nav| nav.push(screen))
+   }
+   pub fn push_with(screen: impl Screen<S> + 'static, transition: Transition) -> Self {
+      Self::nav(|nav| nav.push_with(screen, transition))
+   }
+   pub fn pop() -> Self {
+      Self::nav(|nav| nav.pop())
+   }
+   pub fn pop_with(transition: Transition) -> Self {
+      Self::nav(|nav| nav.pop_with(transition))
+   }
+   pub fn replace(screen: impl Screen<S> + 'static) -> Self {
+      Self::nav(|nav| nav.replace(screen))
+   }
+   pub fn reset(screen: impl Screen<S> + 'static) -> Self {
+      Self::nav(|nav| nav.reset(screen))
+   }
+}
+
+impl<M, S> std::fmt::Debug for ElmCommand<M, S> {
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      match self {
+         ElmCommand::None => write!(f, "None"),
+         ElmCommand::Batch(cmds) => f.debug_tuple("Batch").field(cmds).finish(),
+         ElmCommand::Message(_) => write!(f, "Message"),
+         ElmCommand::After(d, _) => f.debug_tuple("After").field(d).finish(),
+         ElmCommand::Every(h, d, _) => f.debug_tuple("Every").field(h).field(d).finish(),
+         ElmCommand::Cancel(h) => f.debug_tuple("Cancel").field(h).finish(),
+         ElmCommand::Task(name, _) => f.debug_tuple("Task").field(name).finish(),
+         ElmCommand::Nav(_) => write!(f, "Nav"),
+         ElmCommand::Reload => write!(f, "Reload"),
+         ElmCommand::Exit => write!(f, "Exit"),
+      }
+   }
+}
+
+// events become messages, messages go through `update`, and everything `update` wants
+// done is returned as an `ElmCommand`. run it with `TUI::<Elm<MyApp>>::run()`.
+// screens pushed with `ElmCommand::push` share the app as their state, while one is open
+// it gets the input instead of `event` and draws over `view`
+pub trait ElmApp: Sized {
+   type Message: Send + 'static;
+   const APP_NAME: &'static str;
+   const CONFIG_FILE: Option<&'static str>;
+   const DEFAULT_CONFIG_SRC: &'static str;
+   const CONFIG_VERSION: u32 = 0;
+   fn init(tui: TUIMutRef) -> (Self, ElmCommand<Self::Message, Self>);
+   fn event(&self, event: Event) -> Option<Self::Message>;
+   fn update(&mut self, tui: TUIMutRef, msg: Self::Message) -> ElmCommand<Self::Message, Self>;
+   fn view(&self, tui: TUIRef, buf: &mut Buffer);
+   fn migrations() -> Vec<Migration> {
+      Vec::new()
+   }
+}
+
+// adapts an ElmApp to App, keeping track of the timers and tasks its commands started
+pub struct Elm<E: ElmApp> {
+   pub app: E,
+   pub screens: ScreenStack<E>,
+   after: HashMap<String, E::Message>,
+   every: HashMap<String, fn() -> E::Message>,
+   tasks: HashSet<TaskId>,
+   next_timer: u64,
+}
+
+impl<E: ElmApp> Elm<E> {
+   fn after_id(&mut self) -> String {
+      self.next_timer += 1;
+      format!("elm:after:{}", self.next_timer)
+   }
+
+   fn exec(
+      &mut self,
+      tui: &mut TUIMutRef,
+      cmd: ElmCommand<E::Message, E>,
+      queue: &mut VecDeque<E::Message>,
+   ) {
+      match cmd {
+         ElmCommand::None => {}
+         ElmCommand::Batch(cmds) => {
+            for cmd in cmds {
+               self.exec(tui, cmd, queue);
+            }
+         }
+         ElmCommand::Message(msg) => queue.push_back(msg),
+         ElmCommand::After(delay, msg) => {
+            let id = self.after_id();
+            tui.after(delay, &id);
+            self.after.insert(id, msg);
+         }
+         ElmCommand::Every(handle, interval, f) => {
+            let id = handle.timer_id();
+            tui.every(interval, &id);
+            self.every.insert(id, f);
+         }
+         ElmCommand::Cancel(handle) => {
+            let id = handle.timer_id();
+            tui.cancel(&id);
+            self.every.remove(&id);
+         }
+         ElmCommand::Task(name, f) => {
+            let id = tui.spawn(&name, move |ctx| Ok(f(ctx)));
+            self.tasks.insert(id);
+         }
+         ElmCommand::Nav(nav) => self.screens.navigate(nav, &mut self.app, tui.reborrow()),
+         ElmCommand::Reload => tui.runtime.request_reload(),
+         ElmCommand::Exit => tui.runtime.request_exit(),
+      }
+   }
+
+   fn run(&mut self, mut tui: TUIMutRef, mut queue: VecDeque<E::Message>) {
+      let mut handled = 0;
+      while let Some(msg) = queue.pop_front() {
+         if handled == MAX_MESSAGES_PER_TICK {
+            tui.debug.warn(&format!(
+               "{} messages dropped, more than {MAX_MESSAGES_PER_TICK} in one tick",
+               queue.len() + 1
+            ));
+            break;
+         }
+         handled += 1;
+         let cmd = self.app.update(tui.reborrow(), msg);
+         self.exec(&mut tui, cmd, &mut queue);
+      }
+   }
+}
+
+impl<E: ElmApp> App for Elm<E> {
+   const APP_NAME: &'static str = E::APP_NAME;
+   const CONFIG_FILE: Option<&'static str> = E::CONFIG_FILE;
+   const DEFAULT_CONFIG_SRC: &'static str = E::DEFAULT_CONFIG_SRC;
+   const CONFIG_VERSION: u32 = E::CONFIG_VERSION;
+
+   fn init(mut tui: TUIMutRef) -> Self {
+      let (app, cmd) = E::init(tui.reborrow());
+      let mut elm = Elm {
+         app,
+         screens: ScreenStack::empty(),
+         after: HashMap::new(),
+         every: HashMap::new(),
+         tasks: HashSet::new(),
+         next_timer: 0,
+      };
+      let mut queue = VecDeque::new();
+      elm.exec(&mut tui, cmd, &mut queue);
+      elm.run(tui, queue);
+      elm
+   }
+
+   fn logic(&mut self, mut tui: TUIMutRef, mut event: Option<Event>) {
+      if !self.screens.is_empty() {
+         self.screens.step(&mut self.app, tui.reborrow(), event.take());
+      }
+      let mut queue = VecDeque::new();
+      for id in tui.fired_timers() {
+         if let Some(msg) = self.after.remove(&id) {
+            queue.push_back(msg);
+         } else if let Some(f) = self.every.get(&id) {
+            queue.push_back(f());
+         }
+      }
+      for event in tui.tasks.take_events() {
+         if !self.tasks.contains(&event.id()) {
+            continue;
+         }
+         if event.is_finished() {
+            self.tasks.remove(&event.id());
+         }
+         if let TaskEvent::Done { result, .. } = event
+            && let Ok(msg) = result.downcast::<E::Message>()
+         {
+            queue.push_back(*msg);
+         }
+      }
+      if let Some(msg) = event.and_then(|e| self.app.event(e)) {
+         queue.push_back(msg);
+      }
+      self.run(tui, queue);
+   }
+
+   fn render(&self, tui: TUIRef, buf: &mut Buffer) {
+      if !self.screens.covers() {
+         self.app.view(tui, buf);
+      }
+      let area = buf.area;
+      self.screens.render(&self.app, tui, area, buf);
+   }
+
+   fn migrations() -> Vec<Migration> {
+      E::migrations()
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::tui::TestTui;
+   use ratatui::crossterm::event::{KeyCode, KeyEvent};
+   use ratatui::layout::Rect;
+
+   #[derive(Debug)]
+   enum Msg {
+      Tick,
+      Stop,
+      Later(u32),
+      Echo(u32),
+      Open,
+   }
+
+   #[derive(Default)]
+   struct Counter {
+      ticks: u32,
+      seen: Vec<u32>,
+      timer: Option<TimerHandle>,
+   }
+
+   impl ElmApp for Counter {
+      type Message = Msg;
+      const APP_NAME: &'static str = "elm_test";
+      const CONFIG_FILE: Option<&'static str> = None;
+      const DEFAULT_CONFIG_SRC: &'static str = "";
+
+      fn init(_tui: TUIMutRef) -> (Self, ElmCommand<Msg, Self>) {
+         let (every, handle) = ElmCommand::every(Duration::from_millis(10), || Msg::Tick);
+         let after = ElmCommand::After(Duration::from_millis(15), Msg::Later(7));
+         let app = Counter {
+            timer: Some(handle),
+            ..Default::default()
+         };
+         (app, ElmCommand::batch([every, after]))
+      }
+      fn event(&self, event: Event) -> Option<Msg> {
+         matches!(event, Event::Key(_)).then_some(Msg::Tick)
+      }
+      fn update(&mut self, _tui: TUIMutRef, msg: Msg) -> ElmCommand<Msg, Self> {
+         match msg {
+            Msg::Tick => self.ticks += 1,
+            Msg::Stop => return self.timer.take().map_or(ElmCommand::None, ElmCommand::Cancel),
+            Msg::Later(n) => return ElmCommand::Message(Msg::Echo(n + 1)),
+            Msg::Echo(n) => self.seen.push(n),
+            Msg::Open => return ElmCommand::push(Dialog),
+         }
+         ElmCommand::none()
+      }
+      fn view(&self, _tui: TUIRef, _buf: &mut Buffer) {}
+   }
+
+   // records the keys it gets and closes on esc
+   struct Dialog;
+
+   impl Screen<Counter> for Dialog {
+      fn name(&self) -> &str {
+         "dialog"
+      }
+      fn logic(
+         &mut self,
+         state: &mut Counter,
+         _tui: TUIMutRef,
+         event: Option<Event>,
+         nav: &mut Nav<Counter>,
+      ) {
+         if let Some(Event::Key(key)) = event {
+            match key.code {
+               KeyCode::Esc => nav.pop(),
+               _ => state.seen.push(0),
+            }
+         }
+      }
+      fn render(&self, _state: &Counter, _tui: TUIRef, _area: Rect, _buf: &mut Buffer) {}
+   }
+
+   fn step(t: &mut TestTui, elm: &mut Elm<Counter>, ms: u64) {
+      t.timers().advance(Duration::from_millis(ms), None);
+      elm.logic(t.tui(), None);
+   }
+
+   #[test]
+   fn timers_turn_into_messages() {
+      let mut t = TestTui::new();
+      let mut elm = Elm::<Counter>::init(t.tui());
+      step(&mut t, &mut elm, 10);
+      assert_eq!(elm.app.ticks, 1);
+      step(&mut t, &mut elm, 20);
+      assert_eq!(elm.app.ticks, 2);
+      assert_eq!(elm.app.seen, vec![8]);
+   }
+
+   #[test]
+   fn cancel_stops_an_every_timer() {
+      let mut t = TestTui::new();
+      let mut elm = Elm::<Counter>::init(t.tui());
+      step(&mut t, &mut elm, 10);
+      let cmd = elm.app.update(t.tui(), Msg::Stop);
+      elm.exec(&mut t.tui(), cmd, &mut VecDeque::new());
+      assert!(elm.every.is_empty());
+      step(&mut t, &mut elm, 40);
+      assert_eq!(elm.app.ticks, 1);
+   }
+
+   #[test]
+   fn handles_are_unique() {
+      let (_, a) = ElmCommand::<()>::every(Duration::from_secs(1), || ());
+      let (_, b) = ElmCommand::<()>::every(Duration::from_secs(1), || ());
+      assert_ne!(a, b);
+   }
+
+   #[test]
+   fn pushed_screens_take_the_input_until_popped() {
+      let mut t = TestTui::new();
+      let mut elm = Elm::<Counter>::init(t.tui());
+      let key = |code| Some(Event::Key(KeyEvent::from(code)));
+      elm.logic(t.tui(), key(KeyCode::Enter));
+      assert_eq!(elm.app.ticks, 1);
+
+      let cmd = elm.app.update(t.tui(), Msg::Open);
+      elm.exec(&mut t.tui(), cmd, &mut VecDeque::new());
+      assert_eq!(elm.screens.top(), Some("dialog"));
+      elm.logic(t.tui(), key(KeyCode::Enter));
+      assert_eq!((elm.app.ticks, elm.app.seen.as_slice()), (1, &[0][..]));
+
+      elm.logic(t.tui(), key(KeyCode::Esc));
+      assert!(elm.screens.is_empty());
+      assert!(t.runtime.is_running());
+      elm.logic(t.tui(), key(KeyCode::Enter));
+      assert_eq!(elm.app.ticks, 2);
+   }
+}
@@ -1,20 +1,38 @@
+use crate::{parse_color, parse_hex, Theme};
 use kolor::RGB;
 use ratatui::prelude::{Color, Modifier};
 use ratatui::style::Style;
+use ratatui::text::{Line, Span, Text};
 
 pub trait Stylify {
    fn bold(&mut self);
    fn italic(&mut self);
    fn strike(&mut self);
    fn underline(&mut self);
+   fn dim(&mut self);
+   fn reverse(&mut self);
+   fn blink(&mut self);
    fn fore(&mut self, rgb: RGB);
    fn back(&mut self, rgb: RGB);
+   fn fore_color(&mut self, color: Color);
+   fn back_color(&mut self, color: Color);
+   fn under_color(&mut self, color: Color);
    fn from_fg(fg: RGB) -> Self;
    fn from_bg(bg: RGB) -> Self;
    fn from_fg_bg(fg: RGB, bg: RGB) -> Self;
+   // invalid hex gives a plain style, the try_ versions say what was wrong
    fn from_hex(fg: &str) -> Self;
    fn from_hex_bg(bg: &str) -> Self;
    fn from_hex_fg_bg(fg: &str, bg: &str) -> Self;
+   fn try_from_hex(fg: &str) -> Result<Self, String>
+   where
+      Self: Sized;
+   fn try_from_hex_bg(bg: &str) -> Result<Self, String>
+   where
+      Self: Sized;
+   fn try_from_hex_fg_bg(fg: &str, bg: &str) -> Result<Self, String>
+   where
+      Self: Sized;
 }
 
 pub fn rgb_to_color(rgb: RGB) -> Color {
@@ -39,13 +57,32 @@ impl Stylify for Style {
    fn underline(&mut self) {
       *self = self.add_modifier(Modifier::UNDERLINED)
    }
+   fn dim(&mut self) {
+      *self = self.add_modifier(Modifier::DIM)
+   }
+   fn reverse(&mut self) {
+      *self = self.add_modifier(Modifier::REVERSED)
+   }
+   fn blink(&mut self) {
+      *self = self.add_modifier(Modifier::SLOW_BLINK)
+   }
 
    fn fore(&mut self, rgb: RGB) {
-      *self = self.fg(rgb_to_color(rgb))
+      self.fore_color(rgb_to_color(rgb))
    }
 
    fn back(&mut self, rgb: RGB) {
-      *self = self.bg(rgb_to_color(rgb))
+      self.back_color(rgb_to_color(rgb))
+   }
+
+   fn fore_color(&mut self, color: Color) {
+      *self = self.fg(color)
+   }
+   fn back_color(&mut self, color: Color) {
+      *self = self.bg(color)
+   }
+   fn under_color(&mut self, color: Color) {
+      *self = self.underline_color(color)
    }
 
    fn from_fg(fg: RGB) -> Self {
@@ -69,23 +106,219 @@ impl Stylify for Style {
    }
 
    fn from_hex(fg: &str) -> Self {
-      let mut s = Style::default();
-      s.fore(RGB::from_hex(fg));
-      s
+      Self::try_from_hex(fg).unwrap_or_default()
    }
 
    fn from_hex_bg(bg: &str) -> Self {
-      let mut s = Style::default();
-      let bg = RGB::from_hex(bg);
-      s.fore(bg.contrasty());
-      s.back(bg);
-      s
+      Self::try_from_hex_bg(bg).unwrap_or_default()
    }
 
    fn from_hex_fg_bg(fg: &str, bg: &str) -> Self {
-      let mut s = Style::default();
-      s.fore(RGB::from_hex(fg));
-      s.back(RGB::from_hex(bg));
-      s
+      Self::try_from_hex_fg_bg(fg, bg).unwrap_or_default()
+   }
+
+   fn try_from_hex(fg: &str) -> Result<Self, String> {
+      Ok(Self::from_fg(parse_hex(fg)?))
+   }
+
+   fn try_from_hex_bg(bg: &str) -> Result<Self, String> {
+      Ok(Self::from_bg(parse_hex(bg)?))
+   }
+
+   fn try_from_hex_fg_bg(fg: &str, bg: &str) -> Result<Self, String> {
+      Ok(Self::from_fg_bg(parse_hex(fg)?, parse_hex(bg)?))
+   }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkupError {
+   pub pos: usize,
+   pub msg: String,
+}
+
+impl std::fmt::Display for MarkupError {
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "markup error at {}: {}", self.pos, self.msg)
+   }
+}
+
+// a theme slot, then `#rgb`, `#rrggbb` or anything ratatui can parse ("red", "42")
+pub fn markup_color(value: &str, theme: &Theme) -> Option<Color> {
+   theme.get(value).or_else(|| parse_color(value).ok())
+}
+
+fn markup_tag(tag: &str, mut style: Style, theme: &Theme) -> Result<Style, String> {
+   for attr in tag.split_whitespace() {
+      match attr.split_once('=') {
+         Some((key, value)) => {
+            let color = match markup_color(value, theme) {
+               Some(c) => c,
+               None => return Err(format!("unknown color {value:?}")),
+            };
+            match key {
+               "fg" => style.fore_color(color),
+               "bg" => style.back_color(color),
+               "ul" => style.under_color(color),
+               _ => return Err(format!("unknown attribute {key:?}")),
+            }
+         }
+         None => match attr {
+            "b" | "bold" => style.bold(),
+            "i" | "italic" => style.italic(),
+            "u" | "underline" => style.underline(),
+            "s" | "strike" => style.strike(),
+            "d" | "dim" => style.dim(),
+            "r" | "reverse" => style.reverse(),
+            "blink" => style.blink(),
+            // a bare slot name sets the foreground, `[warn]..[/]`
+            _ => match theme.get(attr) {
+               Some(color) => style.fore_color(color),
+               None => return Err(format!("unknown tag {attr:?}")),
+            },
+         },
+      }
+   }
+   Ok(style)
+}
+
+// `[b fg=#ff0 bg=accent]Warning[/] disk at [i]93%[/]`, `[/]` closes the innermost tag,
+// `[[` is a literal `[` and tags left open are closed at the end
+pub fn markup(src: &str, theme: &Theme) -> Result<Text<'static>, MarkupError> {
+   let mut lines = Vec::new();
+   let mut spans = Vec::new();
+   let mut stack = vec![Style::default()];
+   let mut text = String::new();
+   let mut chars = src.char_indices().peekable();
+
+   let flush = |text: &mut String, spans: &mut Vec<Span<'static>>, style: Style| {
+      if !text.is_empty() {
+         spans.push(Span::styled(std::mem::take(text), style));
+      }
+   };
+
+   while let Some((pos, c)) = chars.next() {
+      let style = stack.last().copied().unwrap_or_default();
+      match c {
+         '[' if chars.peek().is_some_and(|(_, n)| *n == '[') => {
+            chars.next();
+            text.push('[');
+         }
+         '[' => {
+            let mut tag = String::new();
+            let mut closed = false;
+            for (_, c) in chars.by_ref() {
+               if c == ']' {
+                  closed = true;
+                  break;
+               }
+               tag.push(c);
+            }
+            if !closed {
+               return Err(MarkupError {
+                  pos,
+                  msg: "unclosed tag".to_string(),
+               });
+            }
+            flush(&mut text, &mut spans, style);
+            if tag.trim() == "/" {
+               if stack.len() == 1 {
+                  return Err(MarkupError {
+                     pos,
+                     msg: "[/] without an open tag".to_string(),
+                  });
+               }
+               stack.pop();
+            } else {
+               match markup_tag(&tag, style, theme) {
+                  Ok(style) => stack.push(style),
+                  Err(msg) => return Err(MarkupError { pos, msg }),
+               }
+            }
+         }
+         '\n' => {
+            flush(&mut text, &mut spans, style);
+            lines.push(Line::from(std::mem::take(&mut spans)));
+         }
+         c => text.push(c),
+      }
+   }
+   flush(&mut text, &mut spans, stack.last().copied().unwrap_or_default());
+   lines.push(Line::from(spans));
+   Ok(Text::from(lines))
+}
+
+pub fn markup_line(src: &str, theme: &Theme) -> Result<Line<'static>, MarkupError> {
+   let text = markup(&src.replace('\n', " "), theme)?;
+   Ok(text.lines.into_iter().next().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn spans(text: &Text) -> Vec<(String, Style)> {
+      text.lines
+         .iter()
+         .flat_map(|l| l.spans.iter())
+         .map(|s| (s.content.to_string(), s.style))
+         .collect()
+   }
+
+   #[test]
+   fn tags_nest_and_close() {
+      let theme = Theme::dark();
+      let text = markup("a[b]b[i fg=#f00]c[/]d[/]e", &theme).unwrap();
+      let bold = Style::default().add_modifier(Modifier::BOLD);
+      let both = bold.add_modifier(Modifier::ITALIC).fg(Color::Rgb(255, 0, 0));
+      assert_eq!(
+         spans(&text),
+         vec![
+            ("a".to_string(), Style::default()),
+            ("b".to_string(), bold),
+            ("c".to_string(), both),
+            ("d".to_string(), bold),
+            ("e".to_string(), Style::default()),
+         ]
+      );
+   }
+
+   #[test]
+   fn slots_escapes_and_lines() {
+      let theme = Theme::dark();
+      let text = markup("[[x]\n[warn]w", &theme).unwrap();
+      assert_eq!(text.lines.len(), 2);
+      assert_eq!(text.lines[0].spans[0].content, "[x]");
+      assert_eq!(text.lines[1].spans[0].style.fg, theme.get("warn"));
+      assert_eq!(markup_line("a\nb", &theme).unwrap().spans[0].content, "a b");
+   }
+
+   #[test]
+   fn errors_carry_the_position() {
+      let theme = Theme::dark();
+      assert_eq!(markup("ab[b", &theme).unwrap_err().pos, 2);
+      assert_eq!(markup("[/]", &theme).unwrap_err().pos, 0);
+      assert!(markup("[fg=nope]x", &theme).is_err());
+      assert!(markup("[zz=red]x", &theme).is_err());
+      assert!(markup("[nope]x", &theme).is_err());
+   }
+
+   #[test]
+   fn markup_colors_prefer_theme_slots() {
+      let theme = Theme::dark();
+      assert_eq!(markup_color("accent", &theme), theme.get("accent"));
+      assert_eq!(markup_color("#fff", &theme), Some(Color::Rgb(255, 255, 255)));
+      assert_eq!(markup_color("7", &theme), Some(Color::Indexed(7)));
+   }
+
+   #[test]
+   fn hex_styles_report_bad_hex() {
+      assert_eq!(Style::try_from_hex("#0f0").unwrap().fg, Some(Color::Rgb(0, 255, 0)));
+      let both = Style::try_from_hex_fg_bg("ff0000", "#fff").unwrap();
+      let white = Color::Rgb(255, 255, 255);
+      assert_eq!((both.fg, both.bg), (Some(Color::Rgb(255, 0, 0)), Some(white)));
+      assert!(Style::try_from_hex("#12345").unwrap_err().contains("3 or 6 digits"));
+      assert!(Style::try_from_hex_bg("#-12").is_err());
+      assert!(Style::try_from_hex_fg_bg("#fff", "nope").unwrap_err().contains("\"nope\""));
+      assert_eq!(Style::from_hex("#zzz"), Style::default());
    }
 }
@@ -0,0 +1,413 @@
+use crate::{color_rgb, parse_color, LuaColor, LuaError, LuaResult, LuaValue};
+use mlua::{FromLua, Lua};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::prelude::Color;
+use ratatui::text::{Line, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GradientSpace {
+   Rgb,
+   Hsl,
+   #[default]
+   Oklab,
+}
+
+impl GradientSpace {
+   pub fn from_name(name: &str) -> Option<Self> {
+      match name.to_lowercase().as_str() {
+         "rgb" => Some(GradientSpace::Rgb),
+         "hsl" => Some(GradientSpace::Hsl),
+         "oklab" => Some(GradientSpace::Oklab),
+         _ => None,
+      }
+   }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GradientDir {
+   #[default]
+   Horizontal,
+   Vertical,
+   Diagonal,
+}
+
+impl GradientDir {
+   pub fn from_name(name: &str) -> Option<Self> {
+      match name.to_lowercase().as_str() {
+         "horizontal" | "h" => Some(GradientDir::Horizontal),
+         "vertical" | "v" => Some(GradientDir::Vertical),
+         "diagonal" | "d" => Some(GradientDir::Diagonal),
+         _ => None,
+      }
+   }
+}
+
+type Rgb = (f32, f32, f32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+   stops: Vec<(f32, Rgb)>,
+   space: GradientSpace,
+   dir: GradientDir,
+}
+
+impl Gradient {
+   // evenly spaced stops, colors without an rgb value (Reset) are skipped
+   pub fn new(colors: &[Color]) -> Self {
+      let colors: Vec<Rgb> = colors.iter().filter_map(|c| color_rgb(*c)).map(unit).collect();
+      let last = colors.len().saturating_sub(1).max(1) as f32;
+      let stops = colors
+         .into_iter()
+         .enumerate()
+         .map(|(i, c)| (i as f32 / last, c))
+         .collect();
+      Self {
+         stops,
+         space: GradientSpace::default(),
+         dir: GradientDir::default(),
+      }
+   }
+   pub fn with_stops(stops: &[(f32, Color)]) -> Self {
+      let mut stops: Vec<(f32, Rgb)> = stops
+         .iter()
+         .filter_map(|(at, c)| Some((at.clamp(0.0, 1.0), unit(color_rgb(*c)?))))
+         .collect();
+      stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+      Self {
+         stops,
+         space: GradientSpace::default(),
+         dir: GradientDir::default(),
+      }
+   }
+   // anything parse_color takes, "#f80", "light-blue" or a palette index
+   pub fn from_colors(colors: &[&str]) -> Result<Self, String> {
+      let colors = colors
+         .iter()
+         .map(|c| parse_color(c))
+         .collect::<Result<Vec<_>, _>>()?;
+      Ok(Gradient::new(&colors))
+   }
+   pub fn rainbow() -> Self {
+      Gradient::new(&[
+         Color::Rgb(255, 0, 0),
+         Color::Rgb(255, 255, 0),
+         Color::Rgb(0, 255, 0),
+         Color::Rgb(0, 255, 255),
+         Color::Rgb(0, 0, 255),
+         Color::Rgb(255, 0, 255),
+      ])
+      .space(GradientSpace::Hsl)
+   }
+
+   pub fn space(mut self, space: GradientSpace) -> Self {
+      self.space = space;
+      self
+   }
+   pub fn direction(mut self, dir: GradientDir) -> Self {
+      self.dir = dir;
+      self
+   }
+
+   pub fn at(&self, t: f32) -> Color {
+      let t = t.clamp(0.0, 1.0);
+      let (first, last) = match (self.stops.first(), self.stops.last()) {
+         (Some(f), Some(l)) => (f, l),
+         _ => return Color::Reset,
+      };
+      if t <= first.0 {
+         return to_color(first.1);
+      }
+      if t >= last.0 {
+         return to_color(last.1);
+      }
+      let i = self.stops.iter().position(|(at, _)| *at >= t).unwrap_or(1).max(1);
+      let (a_at, a) = self.stops[i - 1];
+      let (b_at, b) = self.stops[i];
+      let local = if b_at > a_at { (t - a_at) / (b_at - a_at) } else { 0.0 };
+      to_color(self.mix(a, b, local))
+   }
+
+   // `n` evenly spaced samples from start to end
+   pub fn colors(&self, n: usize) -> Vec<Color> {
+      let last = n.saturating_sub(1).max(1) as f32;
+      (0..n).map(|i| self.at(i as f32 / last)).collect()
+   }
+
+   fn mix(&self, a: Rgb, b: Rgb, t: f32) -> Rgb {
+      match self.space {
+         GradientSpace::Rgb => lerp3(a, b, t),
+         GradientSpace::Hsl => {
+            let (ha, sa, la) = rgb_to_hsl(a);
+            let (hb, sb, lb) = rgb_to_hsl(b);
+            // take the short way around the hue circle
+            let mut dh = hb - ha;
+            if dh > 180.0 {
+               dh -= 360.0;
+            } else if dh < -180.0 {
+               dh += 360.0;
+            }
+            let h = (ha + dh * t).rem_euclid(360.0);
+            hsl_to_rgb((h, sa + (sb - sa) * t, la + (lb - la) * t))
+         }
+         GradientSpace::Oklab => oklab_to_rgb(lerp3(rgb_to_oklab(a), rgb_to_oklab(b), t)),
+      }
+   }
+
+   // one color per char, the rest of each span's style is kept
+   pub fn span(&self, span: &Span) -> Line<'static> {
+      self.line(&Line::from(span.clone()))
+   }
+   pub fn line(&self, line: &Line) -> Line<'static> {
+      let colors = self.colors(line.spans.iter().map(|s| s.content.chars().count()).sum());
+      let mut colors = colors.into_iter();
+      let spans: Vec<Span<'static>> = line
+         .spans
+         .iter()
+         .flat_map(|span| {
+            span
+               .content
+               .chars()
+               .map(|c| {
+                  let fg = colors.next().unwrap_or_default();
+                  Span::styled(c.to_string(), span.style.fg(fg))
+               })
+               .collect::<Vec<_>>()
+         })
+         .collect();
+      let mut out = Line::from(spans).style(line.style);
+      out.alignment = line.alignment;
+      out
+   }
+
+   fn rect_t(&self, area: Rect, x: u16, y: u16) -> f32 {
+      let tx = (x - area.x) as f32 / area.width.saturating_sub(1).max(1) as f32;
+      let ty = (y - area.y) as f32 / area.height.saturating_sub(1).max(1) as f32;
+      match self.dir {
+         GradientDir::Horizontal => tx,
+         GradientDir::Vertical => ty,
+         GradientDir::Diagonal => (tx + ty) / 2.0,
+      }
+   }
+   pub fn paint(&self, area: Rect, buf: &mut Buffer) {
+      let area = area.intersection(buf.area);
+      for y in area.top()..area.bottom() {
+         for x in area.left()..area.right() {
+            let color = self.at(self.rect_t(area, x, y));
+            if let Some(cell) = buf.cell_mut((x, y)) {
+               cell.fg = color;
+            }
+         }
+      }
+   }
+   pub fn paint_bg(&self, area: Rect, buf: &mut Buffer) {
+      let area = area.intersection(buf.area);
+      for y in area.top()..area.bottom() {
+         for x in area.left()..area.right() {
+            let color = self.at(self.rect_t(area, x, y));
+            if let Some(cell) = buf.cell_mut((x, y)) {
+               cell.bg = color;
+            }
+         }
+      }
+   }
+}
+
+// `{ "#f00", "#00f" }` or
+// `{ stops = { { 0, "red" }, { 0.3, "#0f0" } }, space = "hsl", dir = "diagonal" }`
+impl FromLua for Gradient {
+   fn from_lua(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+      let table = match value {
+         LuaValue::Table(t) => t,
+         other => {
+            return Err(LuaError::FromLuaConversionError {
+               from: other.type_name(),
+               to: "Gradient".to_string(),
+               message: Some("expected a table of colors or stops".to_string()),
+            })
+         }
+      };
+      let stops_table = table.get::<Option<mlua::Table>>("stops")?.unwrap_or_else(|| table.clone());
+      let mut colors = Vec::new();
+      let mut stops = Vec::new();
+      for value in stops_table.sequence_values::<LuaValue>() {
+         match value? {
+            LuaValue::Table(stop) => {
+               let at: f32 = stop.get(1)?;
+               let LuaColor(c) = stop.get(2)?;
+               stops.push((at, c));
+            }
+            other => colors.push(LuaColor::from_lua(other, lua)?.0),
+         }
+      }
+      let mut gradient = match stops.is_empty() {
+         true => Gradient::new(&colors),
+         false => Gradient::with_stops(&stops),
+      };
+      if let Some(name) = table.get::<Option<String>>("space")? {
+         match GradientSpace::from_name(&name) {
+            Some(space) => gradient.space = space,
+            None => return Err(LuaError::runtime(format!("unknown gradient space {name:?}"))),
+         }
+      }
+      if let Some(name) = table.get::<Option<String>>("dir")? {
+         match GradientDir::from_name(&name) {
+            Some(dir) => gradient.dir = dir,
+            None => return Err(LuaError::runtime(format!("unknown gradient direction {name:?}"))),
+         }
+      }
+      Ok(gradient)
+   }
+}
+
+fn unit((r, g, b): (u8, u8, u8)) -> Rgb {
+   (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+}
+
+fn to_color((r, g, b): Rgb) -> Color {
+   let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+   Color::Rgb(c(r), c(g), c(b))
+}
+
+fn lerp3(a: Rgb, b: Rgb, t: f32) -> Rgb {
+   (
+      a.0 + (b.0 - a.0) * t,
+      a.1 + (b.1 - a.1) * t,
+      a.2 + (b.2 - a.2) * t,
+   )
+}
+
+fn rgb_to_hsl((r, g, b): Rgb) -> Rgb {
+   let max = r.max(g).max(b);
+   let min = r.min(g).min(b);
+   let l = (max + min) / 2.0;
+   let d = max - min;
+   if d == 0.0 {
+      return (0.0, 0.0, l);
+   }
+   let s = d / (1.0 - (2.0 * l - 1.0).abs());
+   let h = if max == r {
+      60.0 * ((g - b) / d).rem_euclid(6.0)
+   } else if max == g {
+      60.0 * ((b - r) / d + 2.0)
+   } else {
+      60.0 * ((r - g) / d + 4.0)
+   };
+   (h, s, l)
+}
+
+fn hsl_to_rgb((h, s, l): Rgb) -> Rgb {
+   let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+   let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+   let m = l - c / 2.0;
+   let (r, g, b) = match (h / 60.0) as u32 {
+      0 => (c, x, 0.0),
+      1 => (x, c, 0.0),
+      2 => (0.0, c, x),
+      3 => (0.0, x, c),
+      4 => (x, 0.0, c),
+      _ => (c, 0.0, x),
+   };
+   (r + m, g + m, b + m)
+}
+
+fn to_linear(v: f64) -> f64 {
+   match v <= 0.04045 {
+      true => v / 12.92,
+      false => ((v + 0.055) / 1.055).powf(2.4),
+   }
+}
+
+fn to_srgb(v: f64) -> f64 {
+   match v <= 0.0031308 {
+      true => v * 12.92,
+      false => 1.055 * v.powf(1.0 / 2.4) - 0.055,
+   }
+}
+
+// oklab math runs in f64, the published matrices carry more digits than f32 holds
+fn rgb_to_oklab((r, g, b): Rgb) -> Rgb {
+   let (r, g, b) = (to_linear(r as f64), to_linear(g as f64), to_linear(b as f64));
+   let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+   let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+   let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+   (
+      (0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s) as f32,
+      (1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s) as f32,
+      (0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s) as f32,
+   )
+}
+
+fn oklab_to_rgb((l, a, b): Rgb) -> Rgb {
+   let (l, a, b) = (l as f64, a as f64, b as f64);
+   let l_ = (l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+   let m_ = (l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+   let s_ = (l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+   (
+      to_srgb(4.0767416621 * l_ - 3.3077115913 * m_ + 0.2309699292 * s_) as f32,
+      to_srgb(-1.2684380046 * l_ + 2.6097574011 * m_ - 0.3413193965 * s_) as f32,
+      to_srgb(-0.0041960863 * l_ - 0.7034186147 * m_ + 1.7076147010 * s_) as f32,
+   )
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   const RED: Color = Color::Rgb(255, 0, 0);
+   const BLUE: Color = Color::Rgb(0, 0, 255);
+
+   #[test]
+   fn ends_match_the_outer_stops() {
+      let g = Gradient::new(&[RED, BLUE]);
+      assert_eq!(g.at(0.0), RED);
+      assert_eq!(g.at(1.0), BLUE);
+      assert_eq!(g.at(-3.0), RED);
+      assert_eq!(g.at(7.0), BLUE);
+      assert_eq!(g.space(GradientSpace::Rgb).at(0.5), Color::Rgb(128, 0, 128));
+   }
+
+   #[test]
+   fn stops_are_sorted_and_reset_is_skipped() {
+      let g = Gradient::with_stops(&[(1.0, BLUE), (0.0, RED), (0.5, Color::Reset)]);
+      assert_eq!(g, Gradient::new(&[RED, BLUE]));
+      assert_eq!(Gradient::new(&[]).at(0.5), Color::Reset);
+      assert_eq!(Gradient::new(&[RED]).colors(3), vec![RED; 3]);
+   }
+
+   #[test]
+   fn lines_get_one_color_per_char() {
+      let g = Gradient::new(&[RED, BLUE]);
+      let line = g.line(&Line::from(vec![Span::raw("ab"), Span::raw("c")]));
+      let fgs: Vec<_> = line.spans.iter().map(|s| s.style.fg).collect();
+      assert_eq!(fgs, g.colors(3).into_iter().map(Some).collect::<Vec<_>>());
+      assert_eq!(line.spans.len(), 3);
+   }
+
+   #[test]
+   fn paint_follows_the_direction() {
+      let area = Rect::new(0, 0, 3, 3);
+      let mut buf = Buffer::empty(area);
+      Gradient::new(&[RED, BLUE]).direction(GradientDir::Vertical).paint(area, &mut buf);
+      assert_eq!(buf[(2, 0)].fg, RED);
+      assert_eq!(buf[(0, 2)].fg, BLUE);
+   }
+
+   #[test]
+   fn color_strings_are_parsed_or_reported() {
+      let g = Gradient::from_colors(&["#f00", "blue", "21"]).unwrap();
+      assert_eq!(g, Gradient::new(&[RED, Color::Blue, Color::Indexed(21)]));
+      assert!(Gradient::from_colors(&["#f00", "nope"]).unwrap_err().contains("\"nope\""));
+   }
+
+   #[test]
+   fn from_lua_reads_lists_and_stop_tables() {
+      let lua = Lua::new();
+      let list: Gradient = lua.load(r##"return { "#f00", "#00f" }"##).eval().unwrap();
+      assert_eq!(list, Gradient::new(&[RED, BLUE]));
+      let src = r##"return { stops = { { 1, "#00f" }, { 0, "#f00" } }, space = "rgb" }"##;
+      let stops: Gradient = lua.load(src).eval().unwrap();
+      assert_eq!(stops, Gradient::new(&[RED, BLUE]).space(GradientSpace::Rgb));
+      assert!(lua.load("return 5").eval::<Gradient>().is_err());
+   }
+}
@@ -1,17 +1,111 @@
-use crate::{app_err, App, AppOutput};
-use mlua::Lua;
+use crate::tui::{cfg_version, set_cfg_version, version_lua};
+use crate::{app_err, App, AppOutput, LuaError, LuaResult, LuaTable, LuaValue};
+use mlua::{FromLua, Lua, LuaSerdeExt};
+use serde::de::DeserializeOwned;
+use similar::TextDiff;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub(crate) type CfgPath = Option<String>;
 pub(crate) type CfgSrc = Option<String>;
 pub(crate) type Cfg = Option<Lua>;
 
+pub(crate) const CFG_GLOBAL: &str = "cfg";
+
+#[derive(Debug, Clone, Default)]
+pub struct AppDirs {
+   config: Option<PathBuf>,
+   data: Option<PathBuf>,
+   state: Option<PathBuf>,
+   cache: Option<PathBuf>,
+}
+
+impl AppDirs {
+   pub(crate) fn new<A: App>() -> Self {
+      let app_dir = |dir: Option<PathBuf>| dir.map(|d| d.join(A::APP_NAME));
+      Self {
+         config: app_dir(dirs::config_dir()),
+         data: app_dir(dirs::data_dir()),
+         // state_dir only exists on linux, fall back to the local data dir elsewhere
+         state: app_dir(dirs::state_dir().or_else(dirs::data_local_dir)),
+         cache: app_dir(dirs::cache_dir()),
+      }
+   }
+
+   pub fn config_dir(&self) -> Option<PathBuf> {
+      ensure_dir(&self.config)
+   }
+   pub fn data_dir(&self) -> Option<PathBuf> {
+      ensure_dir(&self.data)
+   }
+   pub fn state_dir(&self) -> Option<PathBuf> {
+      ensure_dir(&self.state)
+   }
+   pub fn cache_dir(&self) -> Option<PathBuf> {
+      ensure_dir(&self.cache)
+   }
+}
+
+fn ensure_dir(dir: &Option<PathBuf>) -> Option<PathBuf> {
+   let dir = dir.as_ref()?;
+   if !dir.exists() {
+      std::fs::create_dir_all(dir).ok()?;
+   }
+   Some(dir.clone())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CfgFormat {
+   Lua,
+   Toml,
+   Json,
+   Yaml,
+}
+
+impl CfgFormat {
+   pub fn from_file(file: &str) -> Self {
+      let ext = file.rsplit_once('.').map(|(_, ext)| ext.to_lowercase());
+      match ext.as_deref() {
+         Some("toml") => CfgFormat::Toml,
+         Some("json") => CfgFormat::Json,
+         Some("yaml") | Some("yml") => CfgFormat::Yaml,
+         _ => CfgFormat::Lua,
+      }
+   }
+   pub fn of<A: App>() -> Self {
+      A::CONFIG_FILE.map_or(CfgFormat::Lua, CfgFormat::from_file)
+   }
+   pub fn is_lua(&self) -> bool {
+      *self == CfgFormat::Lua
+   }
+
+   pub(crate) fn parse(&self, lua: &Lua, src: &str) -> LuaResult<LuaValue> {
+      self.parse_named(lua, "config", src)
+   }
+   // lua errors and profiles point at `name`
+   pub(crate) fn parse_named(&self, lua: &Lua, name: &str, src: &str) -> LuaResult<LuaValue> {
+      match self {
+         CfgFormat::Lua => lua.load(src).set_name(format!("={name}")).eval::<LuaValue>(),
+         CfgFormat::Toml => toml::from_str::<toml::Value>(src)
+            .map_err(LuaError::external)
+            .and_then(|v| lua.to_value(&v)),
+         CfgFormat::Json => serde_json::from_str::<serde_json::Value>(src)
+            .map_err(LuaError::external)
+            .and_then(|v| lua.to_value(&v)),
+         CfgFormat::Yaml => serde_yaml::from_str::<serde_yaml::Value>(src)
+            .map_err(LuaError::external)
+            .and_then(|v| lua.to_value(&v)),
+      }
+   }
+}
+
 pub(crate) fn install_cfg<A: App>() -> AppOutput<CfgPath> {
-   let mut cfg_path = match dirs::config_dir() {
+   let cfg_path = match dirs::config_dir() {
       None => return app_err!("failed to determine config dir"),
       Some(path) => path,
    };
    let mut cfg_app_path = cfg_path.clone();
-   cfg_app_path.push(A::APP_NAME.to_string());
+   cfg_app_path.push(A::APP_NAME);
 
    let ok = AppOutput::ok(Some(cfg_app_path.to_string_lossy().to_string()));
 
@@ -21,26 +115,25 @@ pub(crate) fn install_cfg<A: App>() -> AppOutput<CfgPath> {
    };
 
    let mut cfg_app_file = cfg_app_path.clone();
-   cfg_app_file.push(cfg_file.to_string());
+   cfg_app_file.push(cfg_file);
 
    if cfg_app_file.exists() {
       return ok;
    }
-   if let Some(parent) = cfg_app_file.parent() {
-      if !parent.exists() {
-         if let Err(e) = std::fs::create_dir_all(parent) {
-            return app_err!(
-               "failed to create parent dir {} for config at {:?}: {}",
-               A::APP_NAME,
-               parent,
-               e
-            );
-         }
-      }
-   } else {
+   let Some(parent) = cfg_app_file.parent() else {
       return app_err!("failed to get parent dir of config path");
+   };
+   if !parent.exists()
+      && let Err(e) = std::fs::create_dir_all(parent)
+   {
+      return app_err!(
+         "failed to create parent dir {} for config at {:?}: {}",
+         A::APP_NAME,
+         parent,
+         e
+      );
    }
-   if let Err(e) = std::fs::write(&cfg_app_file, A::DEFAULT_CONFIG_SRC) {
+   if let Err(e) = std::fs::write(&cfg_app_file, default_cfg_src::<A>()) {
       return app_err!(
          "failed to write default config at {:?}: {}",
          cfg_app_file,
@@ -65,3 +158,279 @@ pub(crate) fn read_cfg<A: App>() -> AppOutput<CfgSrc> {
       Err(e) => app_err!("failed to read config at {:?}: {}", cfg_path, e),
    }
 }
+
+pub(crate) fn cfg_file_path<A: App>() -> Option<PathBuf> {
+   let mut cfg_path = dirs::config_dir()?;
+   cfg_path.push(A::APP_NAME);
+   cfg_path.push(A::CONFIG_FILE?);
+   Some(cfg_path)
+}
+
+pub(crate) fn unix_stamp() -> u64 {
+   SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or(0)
+}
+
+// never overwrites an older backup, a counter is added when the name is taken
+pub(crate) fn backup_cfg(path: &Path, tag: &str) -> AppOutput<PathBuf> {
+   let name = |n: u32| {
+      let mut name = path.as_os_str().to_os_string();
+      match n {
+         0 => name.push(format!(".{tag}.bak")),
+         n => name.push(format!(".{tag}.{n}.bak")),
+      }
+      PathBuf::from(name)
+   };
+   let backup = (0..).map(name).find(|p| !p.exists()).unwrap_or_else(|| name(0));
+   match std::fs::copy(path, &backup) {
+      Ok(_) => AppOutput::ok(backup),
+      Err(e) => app_err!("failed to back up config {:?} to {:?}: {}", path, backup, e),
+   }
+}
+
+pub(crate) fn default_cfg_src<A: App>() -> String {
+   let src = A::DEFAULT_CONFIG_SRC;
+   if A::CONFIG_VERSION == 0 {
+      return src.to_string();
+   }
+   let format = CfgFormat::of::<A>();
+   let lua = version_lua();
+   match cfg_version(&lua, src, format) {
+      Ok(None) => {
+         match set_cfg_version(&lua, src, A::CONFIG_VERSION, format) {
+            AppOutput::Ok(src) => src,
+            _ => src.to_string(),
+         }
+      }
+      _ => src.to_string(),
+   }
+}
+
+pub(crate) fn diff_cfg<A: App>() -> AppOutput<String> {
+   let path = match cfg_file_path::<A>() {
+      Some(p) => p,
+      None => return app_err!("{} has no config file", A::APP_NAME),
+   };
+   let user_src = match std::fs::read_to_string(&path) {
+      Ok(src) => src,
+      Err(e) => return app_err!("failed to read config at {:?}: {}", path, e),
+   };
+   let default_src = default_cfg_src::<A>();
+   let user_name = path.to_string_lossy().to_string();
+   let default_name = format!("{} (default)", user_name);
+
+   let diff = TextDiff::from_lines(&user_src, &default_src)
+      .unified_diff()
+      .context_radius(3)
+      .header(&user_name, &default_name)
+      .to_string();
+   AppOutput::ok(diff)
+}
+
+pub(crate) fn write_new_cfg<A: App>() -> AppOutput<PathBuf> {
+   let path = match cfg_file_path::<A>() {
+      Some(p) => p,
+      None => return app_err!("{} has no config file", A::APP_NAME),
+   };
+   let mut name = path.as_os_str().to_os_string();
+   name.push(".new");
+   let new_path = PathBuf::from(name);
+   match std::fs::write(&new_path, default_cfg_src::<A>()) {
+      Ok(_) => AppOutput::ok(new_path),
+      Err(e) => app_err!("failed to write default config at {:?}: {}", new_path, e),
+   }
+}
+
+pub(crate) fn reset_cfg<A: App>() -> AppOutput<PathBuf> {
+   let path = match cfg_file_path::<A>() {
+      Some(p) => p,
+      None => return app_err!("{} has no config file", A::APP_NAME),
+   };
+   let backup = match backup_cfg(&path, &unix_stamp().to_string()) {
+      AppOutput::Ok(b) => b,
+      AppOutput::Err(e) => return AppOutput::Err(e),
+      AppOutput::Nil => return AppOutput::void(),
+   };
+   match std::fs::write(&path, default_cfg_src::<A>()) {
+      Ok(_) => AppOutput::ok(backup),
+      Err(e) => app_err!("failed to reset config at {:?}: {}", path, e),
+   }
+}
+
+// warnings come back for a user config that doesn't evaluate to a table. one that only
+// sets globals returns nil, its globals still work but nothing is merged
+pub(crate) fn eval_cfg<A: App>(lua: &Lua, src: CfgSrc) -> LuaResult<Vec<String>> {
+   let format = CfgFormat::of::<A>();
+   let defaults = format.parse_named(lua, "defaults", A::DEFAULT_CONFIG_SRC)?;
+   let has_src = src.is_some();
+   let user = match src {
+      Some(src) => format.parse_named(lua, A::CONFIG_FILE.unwrap_or("config"), &src)?,
+      None => LuaValue::Nil,
+   };
+   let mut warnings = Vec::new();
+   match &user {
+      LuaValue::Table(_) => {}
+      LuaValue::Nil if has_src && defaults.is_table() => warnings.push(
+         "config returned nothing, return a table to override the defaults".to_string(),
+      ),
+      LuaValue::Nil => {}
+      other => warnings.push(format!(
+         "config evaluated to a {} instead of a table, using the defaults",
+         other.type_name()
+      )),
+   }
+   let merged = match (defaults, user) {
+      (LuaValue::Table(base), LuaValue::Table(over)) => {
+         merge_tables(&base, &over)?;
+         base
+      }
+      (LuaValue::Table(t), _) | (_, LuaValue::Table(t)) => t,
+      _ => lua.create_table()?,
+   };
+   lua.globals().set(CFG_GLOBAL, merged)?;
+   Ok(warnings)
+}
+
+// maps merge key by key. arrays, empty tables and plain values from `over` replace
+// what's in `base`, so `{}` clears a default
+pub(crate) fn merge_tables(base: &LuaTable, over: &LuaTable) -> LuaResult<()> {
+   for pair in over.pairs::<LuaValue, LuaValue>() {
+      let (key, value) = pair?;
+      match (base.raw_get::<LuaValue>(key.clone())?, &value) {
+         (LuaValue::Table(b), LuaValue::Table(o)) if is_map(&b) && is_map(o) => {
+            merge_tables(&b, o)?
+         }
+         _ => base.raw_set(key, value)?,
+      }
+   }
+   Ok(())
+}
+
+fn is_map(t: &LuaTable) -> bool {
+   t.raw_len() == 0 && !t.is_empty()
+}
+
+pub(crate) fn cfg_table(cfg: &Cfg) -> Option<LuaTable> {
+   cfg.as_ref()?.globals().get::<Option<LuaTable>>(CFG_GLOBAL).ok()?
+}
+
+pub(crate) fn cfg_get<V: FromLua>(cfg: &Cfg, path: &str) -> Option<V> {
+   let lua = cfg.as_ref()?;
+   let mut value = LuaValue::Table(cfg_table(cfg)?);
+   for key in path.split('.') {
+      value = match value {
+         LuaValue::Table(t) => t.get::<LuaValue>(key).ok()?,
+         _ => return None,
+      };
+   }
+   V::from_lua(value, lua).ok()
+}
+
+pub(crate) fn cfg_as<T: DeserializeOwned>(cfg: &Cfg) -> Option<T> {
+   let lua = cfg.as_ref()?;
+   lua.from_value(LuaValue::Table(cfg_table(cfg)?)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::{TUIMutRef, TUIRef};
+   use ratatui::buffer::Buffer;
+   use ratatui::crossterm::event::Event;
+
+   fn merged(lua: &Lua, base: &str, over: &str) -> LuaTable {
+      let base: LuaTable = lua.load(base).eval().unwrap();
+      let over: LuaTable = lua.load(over).eval().unwrap();
+      merge_tables(&base, &over).unwrap();
+      base
+   }
+
+   #[test]
+   fn maps_merge_key_by_key() {
+      let lua = Lua::new();
+      let base = "return { a = 1, bar = { x = 1, y = 2 } }";
+      let t = merged(&lua, base, "return { bar = { y = 3 } }");
+      assert_eq!(t.get::<i64>("a").unwrap(), 1);
+      let bar: LuaTable = t.get("bar").unwrap();
+      assert_eq!((bar.get::<i64>("x").unwrap(), bar.get::<i64>("y").unwrap()), (1, 3));
+   }
+
+   #[test]
+   fn arrays_and_empty_tables_replace() {
+      let lua = Lua::new();
+      let base = "return { l = { 1, 2, 3 }, m = { a = 1 } }";
+      let t = merged(&lua, base, "return { l = { 9 }, m = {} }");
+      let l: Vec<i64> = t.get("l").unwrap();
+      assert_eq!(l, vec![9]);
+      let m: LuaTable = t.get("m").unwrap();
+      assert!(m.is_empty());
+      let t = merged(&lua, "return { l = { 1, 2 } }", "return { l = {} }");
+      assert!(t.get::<LuaTable>("l").unwrap().is_empty());
+      let t = merged(&lua, "return { v = { a = 1 } }", "return { v = 5 }");
+      assert_eq!(t.get::<i64>("v").unwrap(), 5);
+   }
+
+   #[test]
+   fn cfg_format_follows_the_extension() {
+      assert_eq!(CfgFormat::from_file("app.TOML"), CfgFormat::Toml);
+      assert_eq!(CfgFormat::from_file("app.yml"), CfgFormat::Yaml);
+      assert_eq!(CfgFormat::from_file("app.json"), CfgFormat::Json);
+      assert_eq!(CfgFormat::from_file("init.lua"), CfgFormat::Lua);
+      assert_eq!(CfgFormat::from_file("noext"), CfgFormat::Lua);
+   }
+
+   #[test]
+   fn every_format_parses_to_a_table() {
+      let lua = Lua::new();
+      let sources = [
+         (CfgFormat::Toml, "a = 1\n[b]\nc = 2\n"),
+         (CfgFormat::Json, "{\"a\": 1, \"b\": {\"c\": 2}}"),
+         (CfgFormat::Yaml, "a: 1\nb:\n  c: 2\n"),
+         (CfgFormat::Lua, "return { a = 1, b = { c = 2 } }"),
+      ];
+      for (format, src) in sources {
+         let t = LuaTable::from_lua(format.parse(&lua, src).unwrap(), &lua).unwrap();
+         assert_eq!(t.get::<i64>("a").unwrap(), 1, "{format:?}");
+         assert_eq!(t.get::<LuaTable>("b").unwrap().get::<i64>("c").unwrap(), 2);
+      }
+   }
+
+   struct Defaults;
+
+   impl App for Defaults {
+      const APP_NAME: &'static str = "io_test";
+      const CONFIG_FILE: Option<&'static str> = Some("init.lua");
+      const DEFAULT_CONFIG_SRC: &'static str = "return { fps = 30, bar = { on = true } }";
+      fn init(_tui: TUIMutRef) -> Self {
+         Defaults
+      }
+      fn logic(&mut self, _tui: TUIMutRef, _event: Option<Event>) {}
+      fn render(&self, _tui: TUIRef, _buf: &mut Buffer) {}
+   }
+
+   fn eval(lua: &Lua, src: Option<&str>) -> Vec<String> {
+      eval_cfg::<Defaults>(lua, src.map(str::to_string)).unwrap()
+   }
+
+   #[test]
+   fn user_tables_merge_over_the_defaults() {
+      let lua = Lua::new();
+      assert!(eval(&lua, Some("return { bar = { on = false } }")).is_empty());
+      let cfg: LuaTable = lua.globals().get(CFG_GLOBAL).unwrap();
+      assert_eq!(cfg.get::<i64>("fps").unwrap(), 30);
+      assert!(!cfg.get::<LuaTable>("bar").unwrap().get::<bool>("on").unwrap());
+   }
+
+   #[test]
+   fn configs_that_return_nothing_are_warned_about() {
+      let lua = Lua::new();
+      assert!(eval(&lua, None).is_empty());
+      let warnings = eval(&lua, Some("fps = 60"));
+      assert_eq!(warnings.len(), 1);
+      assert!(warnings[0].contains("returned nothing"));
+      assert_eq!(lua.globals().get::<i64>("fps").unwrap(), 60);
+      assert!(eval(&lua, Some("return 5"))[0].contains("integer"));
+   }
+}
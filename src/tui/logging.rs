@@ -0,0 +1,125 @@
+use crate::{LogEntry, LogSink, LogSource, MsgType};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+#[derive(Debug)]
+pub(crate) struct LogFile {
+   path: PathBuf,
+   file: File,
+   size: u64,
+   max_size: u64,
+   keep: usize,
+}
+
+impl LogFile {
+   pub(crate) fn open(path: PathBuf) -> Option<Self> {
+      let file = OpenOptions::new()
+         .create(true)
+         .append(true)
+         .open(&path)
+         .ok()?;
+      let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+      Some(Self {
+         path,
+         file,
+         size,
+         max_size: 1024 * 1024,
+         keep: 3,
+      })
+   }
+
+   pub(crate) fn write(&mut self, entry: &LogEntry) {
+      let line = format!(
+         "[{:>10.3}] t{} f{} {:<5} {:<4} {}\n",
+         entry.at.as_secs_f32(),
+         entry.tick,
+         entry.frame,
+         entry.msg.msg_type().name(),
+         entry.source.name(),
+         entry.msg.msg()
+      );
+      if self.size + line.len() as u64 > self.max_size {
+         self.rotate();
+      }
+      if self.file.write_all(line.as_bytes()).is_ok() {
+         self.size += line.len() as u64;
+      }
+   }
+
+   // app.log -> app.log.1 -> app.log.2 ..., dropping anything past `keep`
+   fn rotate(&mut self) {
+      let numbered = |n: usize| {
+         let mut name = self.path.as_os_str().to_os_string();
+         name.push(format!(".{n}"));
+         PathBuf::from(name)
+      };
+      for n in (1..self.keep).rev() {
+         let _ = std::fs::rename(numbered(n), numbered(n + 1));
+      }
+      let _ = std::fs::rename(&self.path, numbered(1));
+      if let Ok(file) = File::create(&self.path) {
+         self.file = file;
+         self.size = 0;
+      }
+   }
+}
+
+#[derive(Default)]
+struct MsgVisitor {
+   message: String,
+   fields: String,
+}
+
+impl Visit for MsgVisitor {
+   fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+      match field.name() {
+         "message" => self.message = format!("{value:?}"),
+         // metadata tracing-log attaches to bridged `log` records
+         name if name.starts_with("log.") => {}
+         name => self.fields.push_str(&format!(" {name}={value:?}")),
+      }
+   }
+}
+
+struct SinkLayer {
+   sink: LogSink,
+}
+
+impl<S: Subscriber> Layer<S> for SinkLayer {
+   fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+      let mut visitor = MsgVisitor::default();
+      event.record(&mut visitor);
+      // bridged `log` records carry their real target and level in fields
+      let normalized = event.normalized_metadata();
+      let meta = normalized.as_ref().unwrap_or_else(|| event.metadata());
+      let typ = match *meta.level() {
+         Level::TRACE => MsgType::Trace,
+         Level::DEBUG => MsgType::Debug,
+         Level::INFO => MsgType::Info,
+         Level::WARN => MsgType::Warn,
+         _ => MsgType::Error,
+      };
+      let msg = format!("{}: {}{}", meta.target(), visitor.message, visitor.fields);
+      self.sink.push(&msg, typ, LogSource::Rust);
+   }
+}
+
+// routes `tracing` and `log` macros into the debug log instead of stderr, returns a warning
+// for each of them the app already installed its own subscriber or logger for
+pub(crate) fn install_tracing(sink: LogSink) -> Vec<String> {
+   let mut warnings = Vec::new();
+   let subscriber = tracing_subscriber::registry().with(SinkLayer { sink });
+   if tracing::subscriber::set_global_default(subscriber).is_err() {
+      warnings.push("a tracing subscriber was already set, not capturing events".to_string());
+   }
+   if tracing_log::LogTracer::init().is_err() {
+      warnings.push("a logger was already set, not capturing log records".to_string());
+   }
+   warnings
+}
@@ -0,0 +1,339 @@
+use crate::tui::{backup_cfg, cfg_file_path, unix_stamp, CfgFormat};
+use crate::{app_err, App, AppOutput, LuaFunction, LuaResult, LuaValue};
+use mlua::Lua;
+
+pub enum MigrationFn {
+   Rust(fn(String) -> AppOutput<String>),
+   // chunk evaluating to `function(src) ... return new_src end`
+   Lua(&'static str),
+}
+
+pub struct Migration {
+   pub to: u32,
+   pub func: MigrationFn,
+}
+
+impl Migration {
+   pub fn rust(to: u32, func: fn(String) -> AppOutput<String>) -> Self {
+      Self {
+         to,
+         func: MigrationFn::Rust(func),
+      }
+   }
+   pub fn lua(to: u32, src: &'static str) -> Self {
+      Self {
+         to,
+         func: MigrationFn::Lua(src),
+      }
+   }
+
+   pub(crate) fn apply(&self, src: String) -> AppOutput<String> {
+      match &self.func {
+         MigrationFn::Rust(f) => f(src),
+         MigrationFn::Lua(chunk) => {
+            let lua = Lua::new();
+            let f = match lua.load(*chunk).eval::<LuaFunction>() {
+               Ok(f) => f,
+               Err(e) => return app_err!("failed to load cfg migration to v{}: {}", self.to, e),
+            };
+            match f.call::<String>(src) {
+               Ok(src) => AppOutput::ok(src),
+               Err(e) => app_err!("failed to run cfg migration to v{}: {}", self.to, e),
+            }
+         }
+      }
+   }
+}
+
+pub(crate) fn migrate_cfg<A: App>() -> AppOutput<()> {
+   // unversioned apps never migrate, no need to evaluate the config for it
+   if A::CONFIG_VERSION == 0 {
+      return AppOutput::void();
+   }
+   let path = match cfg_file_path::<A>() {
+      Some(p) => p,
+      None => return AppOutput::void(),
+   };
+   let mut src = match std::fs::read_to_string(&path) {
+      Ok(src) => src,
+      Err(e) => return app_err!("failed to read config at {:?}: {}", path, e),
+   };
+   let format = CfgFormat::of::<A>();
+   let lua = version_lua();
+   let from = match cfg_version(&lua, &src, format) {
+      Ok(v) => v.unwrap_or(0),
+      // left alone, the error shows up when the config is loaded
+      Err(_) => return AppOutput::void(),
+   };
+   if from >= A::CONFIG_VERSION {
+      return AppOutput::void();
+   }
+
+   let mut migrations = A::migrations();
+   migrations.sort_by_key(|m| m.to);
+   for m in migrations
+      .iter()
+      .filter(|m| m.to > from && m.to <= A::CONFIG_VERSION)
+   {
+      src = match m.apply(src) {
+         AppOutput::Ok(new) => match set_cfg_version(&lua, &new, m.to, format) {
+            AppOutput::Ok(new) => new,
+            AppOutput::Err(e) => return AppOutput::Err(e),
+            AppOutput::Nil => return AppOutput::void(),
+         },
+         AppOutput::Err(e) => return AppOutput::Err(e),
+         AppOutput::Nil => return AppOutput::void(),
+      };
+   }
+   src = match set_cfg_version(&lua, &src, A::CONFIG_VERSION, format) {
+      AppOutput::Ok(src) => src,
+      AppOutput::Err(e) => return AppOutput::Err(e),
+      AppOutput::Nil => return AppOutput::void(),
+   };
+
+   if let AppOutput::Err(e) = backup_cfg(&path, &format!("v{from}-{}", unix_stamp())) {
+      return AppOutput::Err(e);
+   }
+   match std::fs::write(&path, src) {
+      Ok(_) => AppOutput::void(),
+      Err(e) => app_err!("failed to write migrated config at {:?}: {}", path, e),
+   }
+}
+
+// lua configs are evaluated to find their version, with unknown globals like `tui`
+// stubbed out since only the returned table matters. nothing that touches files,
+// processes or other modules is left, so the config's side effects don't run twice
+pub(crate) fn version_lua() -> Lua {
+   let lua = Lua::new();
+   let _ = lua.load(STUB_GLOBALS).exec();
+   lua
+}
+
+const STUB_GLOBALS: &str = r#"
+os, io, require, package, dofile, loadfile = nil, nil, nil, nil, nil, nil
+local stub = {}
+setmetatable(stub, { __index = function() return stub end, __call = function() return stub end })
+setmetatable(_G, { __index = function() return stub end })
+"#;
+
+// the top level `version` key of the parsed config, errors if it doesn't parse
+pub(crate) fn cfg_version(lua: &Lua, src: &str, format: CfgFormat) -> LuaResult<Option<u32>> {
+   match format.parse(lua, src)? {
+      LuaValue::Table(t) => Ok(t.raw_get::<Option<u32>>("version").ok().flatten()),
+      _ => Ok(None),
+   }
+}
+
+// edits the source in place to keep comments and layout, every candidate edit is
+// parsed again and only one that changes the top level version is kept
+pub(crate) fn set_cfg_version(
+   lua: &Lua,
+   src: &str,
+   version: u32,
+   format: CfgFormat,
+) -> AppOutput<String> {
+   let current = match cfg_version(lua, src, format) {
+      Ok(v) => v,
+      Err(e) => return app_err!("failed to read config version: {}", e),
+   };
+   if current == Some(version) {
+      return AppOutput::ok(src.to_string());
+   }
+   let candidates = match current {
+      Some(_) => version_spans(src)
+         .into_iter()
+         .map(|(start, end)| format!("{}{}{}", &src[..start], version, &src[end..]))
+         .collect(),
+      None => insert_version(src, version, format),
+   };
+   let edited = candidates
+      .into_iter()
+      .find(|c| matches!(cfg_version(lua, c, format), Ok(Some(v)) if v == version));
+   match edited {
+      Some(src) => AppOutput::ok(src),
+      None => app_err!("failed to set config version to {}", version),
+   }
+}
+
+// spans of N in every `version = N`, `version: N` or `"version": N`
+fn version_spans(src: &str) -> Vec<(usize, usize)> {
+   let bytes = src.as_bytes();
+   let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+   let skip_ws = |mut i: usize| {
+      while i < bytes.len() && (bytes[i] == b' ' || bytes[i] == b'\t') {
+         i += 1;
+      }
+      i
+   };
+
+   let mut spans = Vec::new();
+   let mut from = 0;
+   while let Some(i) = src[from..].find("version") {
+      let start = from + i;
+      from = start + "version".len();
+      if start > 0 && is_ident(bytes[start - 1]) {
+         continue;
+      }
+      let mut j = from;
+      if j < bytes.len() && bytes[j] == b'"' {
+         j += 1;
+      }
+      j = skip_ws(j);
+      if j >= bytes.len() || (bytes[j] != b'=' && bytes[j] != b':') {
+         continue;
+      }
+      let num_start = skip_ws(j + 1);
+      let mut num_end = num_start;
+      while num_end < bytes.len() && bytes[num_end].is_ascii_digit() {
+         num_end += 1;
+      }
+      if num_end > num_start {
+         spans.push((num_start, num_end));
+      }
+   }
+   spans
+}
+
+// places a version key could go when the config has none, most likely first
+fn insert_version(src: &str, version: u32, format: CfgFormat) -> Vec<String> {
+   match format {
+      // into the table constructor after a `return`, the last one is usually the config
+      CfgFormat::Lua => src
+         .rmatch_indices("return")
+         .filter_map(|(i, _)| {
+            let after = i + "return".len();
+            let brace = src.len() - src[after..].trim_start().len();
+            src[brace..].starts_with('{').then(|| {
+               format!("{}{{ version = {version},{}", &src[..brace], &src[brace + 1..])
+            })
+         })
+         .collect(),
+      CfgFormat::Toml => vec![format!("version = {version}\n{src}")],
+      // after the `---` that opens the first document, directives and comments before it
+      // stay where they are
+      CfgFormat::Yaml => {
+         let mut at = vec![0];
+         let mut offset = 0;
+         for line in src.split_inclusive('\n') {
+            offset += line.len();
+            let trimmed = line.trim();
+            if trimmed == "---" || trimmed.starts_with("--- ") {
+               at.insert(0, offset);
+               break;
+            }
+            if !(trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('%')) {
+               break;
+            }
+         }
+         at.into_iter()
+            .map(|i| {
+               let nl = if i > 0 && !src[..i].ends_with('\n') { "\n" } else { "" };
+               format!("{}{nl}version: {version}\n{}", &src[..i], &src[i..])
+            })
+            .collect()
+      }
+      // json has no comments, the top level object opens at the first non blank character
+      CfgFormat::Json => {
+         let body = src.trim_start_matches(|c: char| c.is_whitespace() || c == '\u{feff}');
+         let i = src.len() - body.len();
+         if !body.starts_with('{') {
+            return Vec::new();
+         }
+         let rest = &src[i + 1..];
+         let sep = if rest.trim_start().starts_with('}') { "" } else { "," };
+         vec![format!("{}{{\n  \"version\": {version}{sep}{rest}", &src[..i])]
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn version(src: &str, format: CfgFormat) -> Option<u32> {
+      cfg_version(&version_lua(), src, format).unwrap()
+   }
+   fn set(src: &str, v: u32, format: CfgFormat) -> String {
+      match set_cfg_version(&version_lua(), src, v, format) {
+         AppOutput::Ok(src) => src,
+         AppOutput::Err(e) => panic!("{e}"),
+         AppOutput::Nil => panic!("nil"),
+      }
+   }
+
+   #[test]
+   fn version_is_read_from_the_top_level_only() {
+      let src = "-- version = 9\nlocal s = 'version = 8'\nreturn { bar = { version = 7 } }";
+      assert_eq!(version(src, CfgFormat::Lua), None);
+      let src = "[dep]\nversion = 3\n";
+      assert_eq!(version(src, CfgFormat::Toml), None);
+      assert_eq!(version("{\"a\": {\"version\": 2}, \"version\": 5}", CfgFormat::Json), Some(5));
+   }
+
+   #[test]
+   fn lua_configs_see_stubbed_globals() {
+      let src = "tui.after(1, function() end)\nreturn { version = 2, x = tui.store.get('x') }";
+      assert_eq!(version(src, CfgFormat::Lua), Some(2));
+   }
+
+   #[test]
+   fn set_replaces_the_top_level_value() {
+      let src = "-- version = 1\nreturn {\n   bar = { version = 1 },\n   version = 1,\n}";
+      let out = set(src, 4, CfgFormat::Lua);
+      assert_eq!(version(&out, CfgFormat::Lua), Some(4));
+      assert!(out.starts_with("-- version = 1\n"));
+      assert!(out.contains("bar = { version = 1 }"));
+   }
+
+   #[test]
+   fn set_inserts_into_the_returned_table() {
+      let src = "local t = { a = 1 }\nreturn { b = 2 }";
+      let out = set(src, 3, CfgFormat::Lua);
+      assert_eq!(version(&out, CfgFormat::Lua), Some(3));
+      assert!(out.starts_with("local t = { a = 1 }\n"));
+      assert_eq!(version(&set("return {}", 1, CfgFormat::Lua), CfgFormat::Lua), Some(1));
+   }
+
+   #[test]
+   fn set_inserts_for_every_format() {
+      let toml = set("# cfg\n[bar]\nversion = 9\n", 2, CfgFormat::Toml);
+      assert_eq!(version(&toml, CfgFormat::Toml), Some(2));
+      let json = set("{\"a\": 1}", 2, CfgFormat::Json);
+      assert_eq!(version(&json, CfgFormat::Json), Some(2));
+      let yaml = set("a: 1\n", 2, CfgFormat::Yaml);
+      assert_eq!(version(&yaml, CfgFormat::Yaml), Some(2));
+   }
+
+   #[test]
+   fn yaml_version_goes_after_the_document_marker() {
+      let out = set("# cfg\n---\na: 1\n", 2, CfgFormat::Yaml);
+      assert_eq!(out, "# cfg\n---\nversion: 2\na: 1\n");
+      assert_eq!(version(&out, CfgFormat::Yaml), Some(2));
+   }
+
+   #[test]
+   fn json_version_goes_into_the_top_level_object() {
+      let out = set("  {}", 1, CfgFormat::Json);
+      assert_eq!(version(&out, CfgFormat::Json), Some(1));
+      let out = set("{\"a\": {\"b\": 1}}", 1, CfgFormat::Json);
+      assert_eq!(version(&out, CfgFormat::Json), Some(1));
+      assert!(out.contains("\"a\": {\"b\": 1}"));
+   }
+
+   #[test]
+   fn migrations_run_on_the_source() {
+      let m = Migration::rust(2, |src| AppOutput::ok(src.replace("old", "new")));
+      assert!(matches!(m.apply("old = 1".into()), AppOutput::Ok(s) if s == "new = 1"));
+      let m = Migration::lua(2, "return function(src) return src:upper() end");
+      assert!(matches!(m.apply("a".into()), AppOutput::Ok(s) if s == "A"));
+   }
+
+   #[test]
+   fn lua_configs_cant_reach_files_or_modules() {
+      let marker = std::env::temp_dir().join(format!("katatui-version-{}", unix_stamp()));
+      let open = format!("local f = io.open('{}', 'w')\n", marker.display());
+      let src = open + "os.execute('true')\nrequire('theme')\nreturn { version = 3 }";
+      assert_eq!(version(&src, CfgFormat::Lua), Some(3));
+      assert!(!marker.exists());
+   }
+}
@@ -1,13 +1,58 @@
+mod anim;
+mod api;
 mod app;
+#[cfg(feature = "async")]
+mod async_app;
+mod bar;
+mod capture;
+mod cli;
+mod color;
+mod component;
+mod console;
+mod convert;
 mod debug;
+mod elm;
 mod fmt;
+mod gradient;
 mod io;
+mod logging;
+mod migrate;
+mod perf;
+mod profile;
 mod runtime;
+mod screen;
+mod store;
+mod task;
+mod theme;
+mod timer;
+#[allow(clippy::module_inception)]
 mod tui;
 
+pub use anim::*;
+pub(crate) use api::*;
 pub use app::*;
+#[cfg(feature = "async")]
+pub use async_app::*;
+pub use bar::*;
+pub use capture::*;
+pub use cli::*;
+pub use color::*;
+pub use component::*;
+pub use console::*;
+pub use convert::*;
 pub use debug::*;
+pub use elm::*;
 pub use fmt::*;
+pub use gradient::*;
 pub use io::*;
+pub(crate) use logging::*;
+pub use migrate::*;
+pub(crate) use perf::*;
+pub use profile::*;
 pub use runtime::*;
+pub use screen::*;
+pub use store::*;
+pub use task::*;
+pub use theme::*;
+pub use timer::*;
 pub use tui::*;
@@ -0,0 +1,109 @@
+use crate::tui::debug::Debug;
+use crate::{Runtime, TimingStats};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Clear, Paragraph, Sparkline};
+use std::collections::VecDeque;
+
+const PANEL_WIDTH: u16 = 64;
+const PANEL_HEIGHT: u16 = 10;
+const PROFILE_ROWS: usize = 5;
+
+fn stats_line(name: &str, stats: &TimingStats, budget: u128) -> Line<'static> {
+   let ms = |us: u128| us as f32 / 1000.0;
+   let over_style = match stats.overruns {
+      0 => Style::default().fg(Color::Green),
+      _ => Style::default().fg(Color::Red),
+   };
+   Line::from(vec![
+      Span::styled(format!("{name:<5}"), Style::default().add_modifier(Modifier::BOLD)),
+      Span::raw(format!(
+         " p50 {:.2} p95 {:.2} p99 {:.2} max {:.2} ms ",
+         ms(stats.p50),
+         ms(stats.p95),
+         ms(stats.p99),
+         ms(stats.max)
+      )),
+      Span::styled(
+         format!("over {:.1}ms: {}/{}", ms(budget), stats.overruns, stats.samples),
+         over_style,
+      ),
+   ])
+}
+
+fn recent(samples: &VecDeque<u128>, width: u16) -> Vec<u64> {
+   let skip = samples.len().saturating_sub(width as usize);
+   samples.iter().skip(skip).map(|t| *t as u64).collect()
+}
+
+fn profile_lines(debug: &Debug) -> Vec<Line<'static>> {
+   let ms = |d: std::time::Duration| d.as_secs_f32() * 1000.0;
+   let fns = debug.profiler.by_total();
+   if fns.is_empty() {
+      return Vec::new();
+   }
+   let mut lines = vec![Line::from(format!(
+      "{:<14} {:>7} {:>8} {:>8} {:>8}",
+      "lua fn", "calls", "avg ms", "max ms", "alloc kb"
+   ))
+   .style(Style::default().add_modifier(Modifier::BOLD))];
+   for (name, p) in fns.into_iter().take(PROFILE_ROWS) {
+      let style = match p.errors {
+         0 => Style::default(),
+         _ => Style::default().fg(Color::Red),
+      };
+      lines.push(
+         Line::from(format!(
+            "{:<14.14} {:>7} {:>8.3} {:>8.3} {:>8.1}",
+            name,
+            p.calls,
+            ms(p.avg()),
+            ms(p.max),
+            p.alloc as f32 / 1024.0
+         ))
+         .style(style),
+      );
+   }
+   lines
+}
+
+// top right corner of the area left over by the debug bar, clear of the split console
+pub(crate) fn render_perf(runtime: &Runtime, debug: &Debug, area: Rect, buf: &mut Buffer) {
+   let profile = profile_lines(debug);
+   let width = PANEL_WIDTH.min(area.width);
+   let height = (PANEL_HEIGHT + profile.len() as u16).min(area.height);
+   let panel = Rect::new(area.x + area.width - width, area.y, width, height);
+
+   let block = Block::bordered()
+      .title(" perf ")
+      .border_style(Style::default().fg(Color::Magenta));
+   let inner = block.inner(panel);
+   Clear.render(panel, buf);
+   block.render(panel, buf);
+   if inner.height < 4 {
+      return;
+   }
+
+   let [frame_stats, frame_graph, tick_stats, tick_graph, profile_area] = Layout::vertical([
+      Constraint::Length(1),
+      Constraint::Fill(1),
+      Constraint::Length(1),
+      Constraint::Fill(1),
+      Constraint::Length(profile.len() as u16),
+   ])
+   .areas(inner);
+
+   let frame_budget = runtime.budget();
+   let tick_budget = runtime.tick_budget();
+   Paragraph::new(stats_line("frame", &runtime.frame_stats(), frame_budget))
+      .render(frame_stats, buf);
+   Sparkline::default()
+      .data(recent(runtime.frame_times(), frame_graph.width))
+      .style(Style::default().fg(Color::LightMagenta))
+      .render(frame_graph, buf);
+   Paragraph::new(stats_line("tick", &runtime.tick_stats(), tick_budget)).render(tick_stats, buf);
+   Sparkline::default()
+      .data(recent(runtime.tick_times(), tick_graph.width))
+      .style(Style::default().fg(Color::Cyan))
+      .render(tick_graph, buf);
+   Paragraph::new(profile).render(profile_area, buf);
+}
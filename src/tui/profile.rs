@@ -0,0 +1,160 @@
+use crate::tui::Cfg;
+use crate::tui::debug::Debug;
+use crate::{app_err, AppOutput, LogSource, LuaFunction, MsgType};
+use mlua::{FromLuaMulti, IntoLuaMulti, Lua};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FnProfile {
+   pub calls: u64,
+   pub errors: u64,
+   pub total: Duration,
+   pub max: Duration,
+   // bytes the lua heap grew by during calls, collections mid-call hide some
+   pub alloc: u64,
+   pub max_alloc: u64,
+}
+
+impl FnProfile {
+   pub fn avg(&self) -> Duration {
+      match self.calls {
+         0 => Duration::ZERO,
+         n => self.total / n as u32,
+      }
+   }
+}
+
+#[derive(Debug, Default)]
+pub struct Profiler {
+   fns: HashMap<String, FnProfile>,
+}
+
+impl Profiler {
+   pub fn get(&self, func: &str) -> Option<&FnProfile> {
+      self.fns.get(func)
+   }
+   // heaviest first
+   pub fn by_total(&self) -> Vec<(&str, &FnProfile)> {
+      let mut fns: Vec<(&str, &FnProfile)> =
+         self.fns.iter().map(|(k, v)| (k.as_str(), v)).collect();
+      fns.sort_by_key(|f| Reverse(f.1.total));
+      fns
+   }
+   pub fn reset(&mut self) {
+      self.fns.clear();
+   }
+
+   pub fn report(&self) -> String {
+      let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+      let mut out = format!(
+         "{:<24} {:>8} {:>6} {:>12} {:>10} {:>10} {:>12}\n",
+         "fn", "calls", "errors", "total ms", "avg ms", "max ms", "alloc kb"
+      );
+      for (name, p) in self.by_total() {
+         out.push_str(&format!(
+            "{:<24} {:>8} {:>6} {:>12.3} {:>10.3} {:>10.3} {:>12.1}\n",
+            name,
+            p.calls,
+            p.errors,
+            ms(p.total),
+            ms(p.avg()),
+            ms(p.max),
+            p.alloc as f64 / 1024.0
+         ));
+      }
+      out
+   }
+   pub fn export(&self, path: &Path) -> AppOutput<()> {
+      match std::fs::write(path, self.report()) {
+         Ok(_) => AppOutput::void(),
+         Err(e) => app_err!("failed to export lua profile to {:?}: {}", path, e),
+      }
+   }
+
+   pub(crate) fn record(&mut self, func: &str, elapsed: Duration, alloc: u64, failed: bool) {
+      let p = self.fns.entry(func.to_string()).or_default();
+      p.calls += 1;
+      p.errors += failed as u64;
+      p.total += elapsed;
+      p.max = p.max.max(elapsed);
+      p.alloc += alloc;
+      p.max_alloc = p.max_alloc.max(alloc);
+   }
+}
+
+// every call into a cfg function goes through here so the profiler sees all of them
+pub(crate) fn call_lua_fn<R: FromLuaMulti>(
+   cfg: &Cfg,
+   debug: &mut Debug,
+   func: &str,
+   args: impl IntoLuaMulti,
+) -> AppOutput<R> {
+   let lua = match cfg {
+      Some(lua) => lua,
+      None => return AppOutput::void(),
+   };
+   let f = match lua.globals().get::<LuaFunction>(func) {
+      Ok(f) => f,
+      Err(e) => return app_err!("failed to run cfg fn {func} {}", e),
+   };
+
+   call_lua_func(lua, debug, func, f, args)
+}
+
+// where an anonymous function was defined, like `init.lua:12`
+pub(crate) fn lua_fn_name(f: &LuaFunction) -> String {
+   let info = f.info();
+   let src = info.short_src.unwrap_or_else(|| "?".to_string());
+   format!("{src}:{}", info.line_defined.unwrap_or(0))
+}
+
+// for functions that aren't cfg globals, like timer callbacks, `name` is what the profiler shows
+pub(crate) fn call_lua_func<R: FromLuaMulti>(
+   lua: &Lua,
+   debug: &mut Debug,
+   name: &str,
+   f: LuaFunction,
+   args: impl IntoLuaMulti,
+) -> AppOutput<R> {
+   let mem_before = lua.used_memory();
+   let start = Instant::now();
+   let result = f.call::<R>(args);
+   let elapsed = start.elapsed();
+   let alloc = lua.used_memory().saturating_sub(mem_before) as u64;
+   debug.profiler.record(name, elapsed, alloc, result.is_err());
+
+   match result {
+      Ok(r) => AppOutput::ok(r),
+      Err(e) => {
+         let msg = format!("cfg fn {name} failed: {e}");
+         debug.push(&msg, MsgType::Error, LogSource::Lua);
+         AppOutput::Err(msg)
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn callbacks_are_profiled_by_where_they_were_defined() {
+      let lua = Lua::new();
+      let mut debug = Debug::new();
+      let src = "return {\n   fast = function() end,\n   slow = function() error('x') end,\n}";
+      let fns: mlua::Table = lua.load(src).set_name("=init.lua").eval().unwrap();
+      for key in ["fast", "slow", "fast"] {
+         let f: LuaFunction = fns.get(key).unwrap();
+         let name = lua_fn_name(&f);
+         let _ = call_lua_func::<()>(&lua, &mut debug, &name, f, ());
+      }
+      let fast = debug.profiler.get("init.lua:2").unwrap();
+      assert_eq!((fast.calls, fast.errors), (2, 0));
+      let slow = debug.profiler.get("init.lua:3").unwrap();
+      assert_eq!((slow.calls, slow.errors), (1, 1));
+      assert_eq!(debug.profiler.by_total().len(), 2);
+   }
+}
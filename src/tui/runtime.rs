@@ -1,5 +1,35 @@
-use crate::{LuaResult, LuaTable};
-use std::time::Instant;
+use crate::{ColorSupport, LuaResult, LuaTable};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimingStats {
+   pub p50: u128,
+   pub p95: u128,
+   pub p99: u128,
+   pub max: u128,
+   pub overruns: usize,
+   pub samples: usize,
+}
+
+impl TimingStats {
+   pub fn from_samples(samples: &VecDeque<u128>, budget: u128) -> Self {
+      if samples.is_empty() {
+         return Self::default();
+      }
+      let mut sorted: Vec<u128> = samples.iter().copied().collect();
+      sorted.sort_unstable();
+      let pct = |p: usize| sorted[((sorted.len() - 1) * p) / 100];
+      Self {
+         p50: pct(50),
+         p95: pct(95),
+         p99: pct(99),
+         max: sorted[sorted.len() - 1],
+         overruns: sorted.iter().filter(|t| **t > budget).count(),
+         samples: sorted.len(),
+      }
+   }
+}
 
 #[derive(Debug)]
 pub struct Runtime {
@@ -13,13 +43,18 @@ pub struct Runtime {
    pub(crate) budget: u128,
    pub(crate) f_ms: u128,
    pub(crate) t_ms: u128,
-   pub(crate) last: Instant,
-   pub(crate) fps_accum: f32,
-   pub(crate) fps_count: u32,
+   // durations in micros of the most recent frames / ticks, oldest first
+   pub(crate) frame_times: VecDeque<u128>,
+   pub(crate) tick_times: VecDeque<u128>,
+   pub(crate) history_len: usize,
+   pub(crate) color_support: ColorSupport,
 
    pub(crate) is_reload: bool,
    pub(crate) was_reload: bool,
    pub(crate) is_debug: bool,
+   pub(crate) is_console: bool,
+   pub(crate) is_perf: bool,
+   pub(crate) is_mouse: bool,
    pub(crate) is_exit: bool,
 }
 
@@ -28,8 +63,10 @@ impl Runtime {
       let now = Instant::now();
       Self {
          start: now,
-         last: now,
-         fps_accum: 0.0,
+         frame_times: VecDeque::new(),
+         tick_times: VecDeque::new(),
+         history_len: 240,
+         color_support: ColorSupport::detect(),
          frame: 0,
          tick: 0,
          t_fps: 16,
@@ -42,8 +79,10 @@ impl Runtime {
          is_reload: false,
          was_reload: false,
          is_debug: false,
+         is_console: false,
+         is_perf: false,
+         is_mouse: false,
          is_exit: false,
-         fps_count: 0,
       }
    }
 
@@ -77,6 +116,9 @@ impl Runtime {
    pub fn target_tps(&self) -> u32 {
       self.t_tps
    }
+   pub fn elapsed(&self) -> Duration {
+      self.start.elapsed()
+   }
    pub fn frame(&self) -> u32 {
       self.frame
    }
@@ -92,6 +134,33 @@ impl Runtime {
    pub fn budget(&self) -> u128 {
       self.budget
    }
+   pub fn tick_budget(&self) -> u128 {
+      1_000_000 / self.t_tps.max(1) as u128
+   }
+   pub fn frame_times(&self) -> &VecDeque<u128> {
+      &self.frame_times
+   }
+   pub fn tick_times(&self) -> &VecDeque<u128> {
+      &self.tick_times
+   }
+   pub fn frame_stats(&self) -> TimingStats {
+      TimingStats::from_samples(&self.frame_times, self.budget)
+   }
+   pub fn tick_stats(&self) -> TimingStats {
+      TimingStats::from_samples(&self.tick_times, self.tick_budget())
+   }
+   pub fn history_len(&self) -> usize {
+      self.history_len
+   }
+   pub fn set_history_len(&mut self, len: usize) {
+      self.history_len = len.max(1);
+      while self.frame_times.len() > self.history_len {
+         self.frame_times.pop_front();
+      }
+      while self.tick_times.len() > self.history_len {
+         self.tick_times.pop_front();
+      }
+   }
 
    pub fn request_reload(&mut self) {
       self.is_reload = true;
@@ -114,6 +183,47 @@ impl Runtime {
    pub fn is_debug(&self) -> bool {
       self.is_debug
    }
+   pub fn toggle_console(&mut self) {
+      self.is_console = !self.is_console;
+   }
+   pub fn is_console(&self) -> bool {
+      self.is_console
+   }
+   pub fn color_support(&self) -> ColorSupport {
+      self.color_support
+   }
+   pub fn set_color_support(&mut self, support: ColorSupport) {
+      self.color_support = support;
+   }
+   pub fn toggle_perf(&mut self) {
+      self.is_perf = !self.is_perf;
+   }
+   pub fn is_perf(&self) -> bool {
+      self.is_perf
+   }
+   // mouse events only reach the app while this is on, the console grabs the mouse on its own
+   pub fn capture_mouse(&mut self, capture: bool) {
+      self.is_mouse = capture;
+   }
+   pub fn is_mouse_captured(&self) -> bool {
+      self.is_mouse || self.is_console
+   }
+
+   pub(crate) fn record_frame(&mut self, micros: u128) {
+      self.f_ms = micros;
+      self.budget = 1_000_000 / self.t_fps.max(1) as u128;
+      if self.frame_times.len() >= self.history_len {
+         self.frame_times.pop_front();
+      }
+      self.frame_times.push_back(micros);
+   }
+   pub(crate) fn record_tick(&mut self, micros: u128) {
+      self.t_ms = micros;
+      if self.tick_times.len() >= self.history_len {
+         self.tick_times.pop_front();
+      }
+      self.tick_times.push_back(micros);
+   }
 
    pub(crate) fn set_reload(&mut self, req: bool) {
       self.is_reload = req;
@@ -121,10 +231,38 @@ impl Runtime {
    pub(crate) fn set_just_reloaded(&mut self, req: bool) {
       self.was_reload = req;
    }
-   pub(crate) fn set_debug(&mut self, dbg: bool) {
-      self.is_debug = dbg;
+   pub(crate) fn set_console(&mut self, console: bool) {
+      self.is_console = console;
    }
-   pub(crate) fn set_exit(&mut self, exit: bool) {
-      self.is_exit = exit;
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn stats_pick_nearest_rank_percentiles() {
+      let samples: VecDeque<u128> = (1..=100).rev().collect();
+      let stats = TimingStats::from_samples(&samples, 90);
+      assert_eq!((stats.p50, stats.p95, stats.p99, stats.max), (50, 95, 99, 100));
+      assert_eq!((stats.overruns, stats.samples), (10, 100));
+   }
+
+   #[test]
+   fn stats_handle_tiny_histories() {
+      let empty = TimingStats::from_samples(&VecDeque::new(), 10);
+      assert_eq!((empty.max, empty.samples), (0, 0));
+      let one = TimingStats::from_samples(&VecDeque::from([7]), 10);
+      assert_eq!((one.p50, one.p99, one.max, one.overruns), (7, 7, 7, 0));
+   }
+
+   #[test]
+   fn shrinking_history_drops_the_oldest_samples() {
+      let mut runtime = Runtime::new();
+      runtime.frame_times = (0..10).collect();
+      runtime.set_history_len(3);
+      assert_eq!(runtime.frame_times(), &VecDeque::from([7, 8, 9]));
+      runtime.set_history_len(0);
+      assert_eq!(runtime.history_len(), 1);
    }
 }
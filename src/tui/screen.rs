@@ -0,0 +1,481 @@
+use crate::{App, Easing, Migration, TUIMutRef, TUIRef};
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::Event;
+use ratatui::layout::Rect;
+use std::time::Duration;
+
+// `S` is the state every screen of an app shares
+pub trait Screen<S> {
+   fn name(&self) -> &str;
+   // when the screen is pushed, or becomes the top again after the one above was popped
+   fn enter(&mut self, _state: &mut S, _tui: TUIMutRef) {}
+   fn leave(&mut self, _state: &mut S, _tui: TUIMutRef) {}
+   fn logic(&mut self, state: &mut S, tui: TUIMutRef, event: Option<Event>, nav: &mut Nav<S>);
+   fn render(&self, state: &S, tui: TUIRef, area: Rect, buf: &mut Buffer);
+   // overlays (dialogs, popups) render on top of the screens below them
+   fn is_overlay(&self) -> bool {
+      false
+   }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TransitionKind {
+   #[default]
+   None,
+   SlideLeft,
+   SlideRight,
+   SlideUp,
+   SlideDown,
+   Wipe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Transition {
+   pub kind: TransitionKind,
+   pub duration: Duration,
+   pub easing: Easing,
+}
+
+impl Transition {
+   pub fn new(kind: TransitionKind, duration: Duration) -> Self {
+      Self {
+         kind,
+         duration,
+         easing: Easing::OutCubic,
+      }
+   }
+   pub fn easing(mut self, easing: Easing) -> Self {
+      self.easing = easing;
+      self
+   }
+}
+
+enum NavOp<S> {
+   Push(Box<dyn Screen<S>>, Transition),
+   Pop(Transition),
+   Replace(Box<dyn Screen<S>>, Transition),
+   Clear(Box<dyn Screen<S>>, Transition),
+}
+
+// stack changes asked for during logic, applied once the current screen is done
+pub struct Nav<S> {
+   ops: Vec<NavOp<S>>,
+}
+
+impl<S> Nav<S> {
+   pub(crate) fn new() -> Self {
+      Nav { ops: Vec::new() }
+   }
+
+   pub fn push(&mut self, screen: impl Screen<S> + 'static) {
+      self.ops.push(NavOp::Push(Box::new(screen), Transition::default()));
+   }
+   pub fn push_with(&mut self, screen: impl Screen<S> + 'static, transition: Transition) {
+      self.ops.push(NavOp::Push(Box::new(screen), transition));
+   }
+   pub fn pop(&mut self) {
+      self.ops.push(NavOp::Pop(Transition::default()));
+   }
+   pub fn pop_with(&mut self, transition: Transition) {
+      self.ops.push(NavOp::Pop(transition));
+   }
+   pub fn replace(&mut self, screen: impl Screen<S> + 'static) {
+      self.ops.push(NavOp::Replace(Box::new(screen), Transition::default()));
+   }
+   pub fn replace_with(&mut self, screen: impl Screen<S> + 'static, transition: Transition) {
+      self.ops.push(NavOp::Replace(Box::new(screen), transition));
+   }
+   // drops the whole stack and starts over from `screen`
+   pub fn reset(&mut self, screen: impl Screen<S> + 'static) {
+      self.ops.push(NavOp::Clear(Box::new(screen), Transition::default()));
+   }
+   pub fn reset_with(&mut self, screen: impl Screen<S> + 'static, transition: Transition) {
+      self.ops.push(NavOp::Clear(Box::new(screen), transition));
+   }
+}
+
+struct Active<S> {
+   transition: Transition,
+   started: Duration,
+   // the screen that went away, kept around until the transition ends
+   leaving: Option<Box<dyn Screen<S>>>,
+   backwards: bool,
+}
+
+pub struct ScreenStack<S> {
+   screens: Vec<Box<dyn Screen<S>>>,
+   active: Option<Active<S>>,
+}
+
+impl<S> ScreenStack<S> {
+   pub fn new(root: impl Screen<S> + 'static) -> Self {
+      Self::from_boxed(Box::new(root))
+   }
+   pub fn from_boxed(root: Box<dyn Screen<S>>) -> Self {
+      Self {
+         screens: vec![root],
+         active: None,
+      }
+   }
+   // for apps that only open screens now and then, like elm apps
+   pub(crate) fn empty() -> Self {
+      Self {
+         screens: Vec::new(),
+         active: None,
+      }
+   }
+
+   pub fn len(&self) -> usize {
+      self.screens.len()
+   }
+   pub fn is_empty(&self) -> bool {
+      self.screens.is_empty()
+   }
+   pub fn top(&self) -> Option<&str> {
+      self.screens.last().map(|s| s.name())
+   }
+   pub fn names(&self) -> Vec<&str> {
+      self.screens.iter().map(|s| s.name()).collect()
+   }
+   pub fn is_transitioning(&self) -> bool {
+      self.active.is_some()
+   }
+   // whether anything below the stack would still show through
+   pub(crate) fn covers(&self) -> bool {
+      self.screens.iter().any(|s| !s.is_overlay())
+   }
+
+   pub fn enter(&mut self, state: &mut S, tui: TUIMutRef) {
+      if let Some(top) = self.screens.last_mut() {
+         top.enter(state, tui);
+      }
+   }
+
+   // only the top screen sees input, an empty stack exits the app
+   pub fn logic(&mut self, state: &mut S, mut tui: TUIMutRef, event: Option<Event>) {
+      self.step(state, tui.reborrow(), event);
+      if self.screens.is_empty() {
+         tui.runtime.request_exit();
+      }
+   }
+
+   pub(crate) fn step(&mut self, state: &mut S, mut tui: TUIMutRef, event: Option<Event>) {
+      let now = tui.runtime.elapsed();
+      if let Some(active) = &self.active
+         && now.saturating_sub(active.started) >= active.transition.duration
+      {
+         self.active = None;
+      }
+      let mut nav = Nav::new();
+      if let Some(top) = self.screens.last_mut() {
+         top.logic(state, tui.reborrow(), event, &mut nav);
+      }
+      self.navigate(nav, state, tui);
+   }
+
+   // runs stack changes that came from outside a screen, popping an empty stack does nothing
+   pub(crate) fn navigate(&mut self, nav: Nav<S>, state: &mut S, mut tui: TUIMutRef) {
+      let now = tui.runtime.elapsed();
+      for op in nav.ops {
+         if matches!(op, NavOp::Pop(_)) && self.screens.is_empty() {
+            continue;
+         }
+         self.apply(op, state, tui.reborrow(), now);
+      }
+   }
+
+   fn apply(&mut self, op: NavOp<S>, state: &mut S, mut tui: TUIMutRef, now: Duration) {
+      let (leaving, transition, backwards) = match op {
+         NavOp::Push(mut screen, transition) => {
+            screen.enter(state, tui.reborrow());
+            self.screens.push(screen);
+            (None, transition, false)
+         }
+         NavOp::Pop(transition) => {
+            let mut leaving = self.screens.pop();
+            if let Some(screen) = leaving.as_mut() {
+               screen.leave(state, tui.reborrow());
+            }
+            if let Some(top) = self.screens.last_mut() {
+               top.enter(state, tui.reborrow());
+            }
+            (leaving, transition, true)
+         }
+         NavOp::Replace(mut screen, transition) => {
+            let mut leaving = self.screens.pop();
+            if let Some(old) = leaving.as_mut() {
+               old.leave(state, tui.reborrow());
+            }
+            screen.enter(state, tui.reborrow());
+            self.screens.push(screen);
+            (leaving, transition, false)
+         }
+         // the old top is what the transition animates away from
+         NavOp::Clear(mut screen, transition) => {
+            let mut leaving = None;
+            while let Some(mut old) = self.screens.pop() {
+               old.leave(state, tui.reborrow());
+               leaving.get_or_insert(old);
+            }
+            screen.enter(state, tui.reborrow());
+            self.screens.push(screen);
+            (leaving, transition, false)
+         }
+      };
+      self.active = match transition.kind {
+         TransitionKind::None => None,
+         _ => Some(Active {
+            transition,
+            started: now,
+            leaving,
+            backwards,
+         }),
+      };
+   }
+
+   // renders from the topmost full screen up through the overlays above it
+   fn render_stack(
+      screens: &[&dyn Screen<S>],
+      state: &S,
+      tui: TUIRef,
+      area: Rect,
+      buf: &mut Buffer,
+   ) {
+      let base = screens.iter().rposition(|s| !s.is_overlay()).unwrap_or(0);
+      for screen in &screens[base..] {
+         screen.render(state, tui, area, buf);
+      }
+   }
+
+   pub fn render(&self, state: &S, tui: TUIRef, area: Rect, buf: &mut Buffer) {
+      let screens: Vec<&dyn Screen<S>> = self.screens.iter().map(|s| s.as_ref()).collect();
+      let active = match &self.active {
+         Some(active) => active,
+         None => return Self::render_stack(&screens, state, tui, area, buf),
+      };
+      let elapsed = tui.runtime.elapsed().saturating_sub(active.started);
+      let length = active.transition.duration.as_secs_f32();
+      let t = match length > 0.0 {
+         true => active.transition.easing.apply(elapsed.as_secs_f32() / length),
+         false => 1.0,
+      };
+
+      // the stack as it was before the change, and as it is now
+      let mut before = Buffer::empty(area);
+      let mut after = Buffer::empty(area);
+      let mut old = screens.clone();
+      match &active.leaving {
+         Some(leaving) if active.backwards => old.push(leaving.as_ref()),
+         Some(leaving) => {
+            if let Some(last) = old.last_mut() {
+               *last = leaving.as_ref();
+            }
+         }
+         None => {
+            old.pop();
+         }
+      }
+      Self::render_stack(&old, state, tui, area, &mut before);
+      Self::render_stack(&screens, state, tui, area, &mut after);
+
+      // popping plays the transition in reverse, the old stack slides off the new one
+      let (under, over, t) = match active.backwards {
+         true => (&after, &before, 1.0 - t),
+         false => (&before, &after, t),
+      };
+      let (w, h) = (area.width as f32, area.height as f32);
+      let offset = |size: f32| ((1.0 - t) * size).round() as i32;
+      blit(under, buf, area, 0, 0);
+      match active.transition.kind {
+         TransitionKind::None => blit(over, buf, area, 0, 0),
+         TransitionKind::SlideLeft => blit(over, buf, area, offset(w), 0),
+         TransitionKind::SlideRight => blit(over, buf, area, -offset(w), 0),
+         TransitionKind::SlideUp => blit(over, buf, area, 0, offset(h)),
+         TransitionKind::SlideDown => blit(over, buf, area, 0, -offset(h)),
+         TransitionKind::Wipe => {
+            let cols = (t * w).round() as u16;
+            blit(over, buf, Rect { width: cols, ..area }, 0, 0);
+         }
+      }
+   }
+}
+
+// copies `src` cells inside `area` into `dst`, shifted by dx/dy and clipped to `area`
+fn blit(src: &Buffer, dst: &mut Buffer, area: Rect, dx: i32, dy: i32) {
+   for y in area.top()..area.bottom() {
+      for x in area.left()..area.right() {
+         let (tx, ty) = (x as i32 + dx, y as i32 + dy);
+         if tx < area.left() as i32 || ty < area.top() as i32 {
+            continue;
+         }
+         if tx >= area.right() as i32 || ty >= area.bottom() as i32 {
+            continue;
+         }
+         let target = dst.cell_mut((tx as u16, ty as u16));
+         if let (Some(cell), Some(target)) = (src.cell((x, y)), target) {
+            *target = cell.clone();
+         }
+      }
+   }
+}
+
+// an app made only of screens, run with `TUI::<Screens<MyApp>>::run()`
+pub trait ScreenApp {
+   type State;
+   const APP_NAME: &'static str;
+   const CONFIG_FILE: Option<&'static str>;
+   const DEFAULT_CONFIG_SRC: &'static str;
+   const CONFIG_VERSION: u32 = 0;
+   fn init(tui: TUIMutRef) -> (Self::State, Box<dyn Screen<Self::State>>);
+   fn migrations() -> Vec<Migration> {
+      Vec::new()
+   }
+}
+
+pub struct Screens<A: ScreenApp> {
+   pub state: A::State,
+   pub stack: ScreenStack<A::State>,
+}
+
+impl<A: ScreenApp> App for Screens<A> {
+   const APP_NAME: &'static str = A::APP_NAME;
+   const CONFIG_FILE: Option<&'static str> = A::CONFIG_FILE;
+   const DEFAULT_CONFIG_SRC: &'static str = A::DEFAULT_CONFIG_SRC;
+   const CONFIG_VERSION: u32 = A::CONFIG_VERSION;
+
+   fn init(mut tui: TUIMutRef) -> Self {
+      let (mut state, root) = A::init(tui.reborrow());
+      let mut stack = ScreenStack::from_boxed(root);
+      stack.enter(&mut state, tui);
+      Screens { state, stack }
+   }
+   fn logic(&mut self, tui: TUIMutRef, event: Option<Event>) {
+      self.stack.logic(&mut self.state, tui, event);
+   }
+   fn render(&self, tui: TUIRef, buf: &mut Buffer) {
+      let area = buf.area;
+      self.stack.render(&self.state, tui, area, buf);
+   }
+   fn migrations() -> Vec<Migration> {
+      A::migrations()
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::tui::TestTui;
+
+   enum Op {
+      Push(&'static str),
+      Overlay(&'static str),
+      Pop,
+      Replace(&'static str),
+      Reset(&'static str),
+   }
+
+   #[derive(Default)]
+   struct State {
+      log: Vec<String>,
+      next: Option<Op>,
+      transition: Transition,
+   }
+
+   struct Named(&'static str, bool);
+
+   impl Screen<State> for Named {
+      fn name(&self) -> &str {
+         self.0
+      }
+      fn enter(&mut self, state: &mut State, _tui: TUIMutRef) {
+         state.log.push(format!("enter {}", self.0));
+      }
+      fn leave(&mut self, state: &mut State, _tui: TUIMutRef) {
+         state.log.push(format!("leave {}", self.0));
+      }
+      fn logic(&mut self, state: &mut State, _: TUIMutRef, _: Option<Event>, nav: &mut Nav<State>) {
+         let t = state.transition;
+         match state.next.take() {
+            Some(Op::Push(name)) => nav.push_with(Named(name, false), t),
+            Some(Op::Overlay(name)) => nav.push(Named(name, true)),
+            Some(Op::Pop) => nav.pop_with(t),
+            Some(Op::Replace(name)) => nav.replace_with(Named(name, false), t),
+            Some(Op::Reset(name)) => nav.reset_with(Named(name, false), t),
+            None => {}
+         }
+      }
+      fn render(&self, _state: &State, _tui: TUIRef, area: Rect, buf: &mut Buffer) {
+         let row = if self.1 { area.y + 1 } else { area.y };
+         buf.set_string(area.x, row, self.0, ratatui::style::Style::default());
+      }
+      fn is_overlay(&self) -> bool {
+         self.1
+      }
+   }
+
+   fn run(t: &mut TestTui, stack: &mut ScreenStack<State>, state: &mut State, op: Op) {
+      state.next = Some(op);
+      stack.logic(state, t.tui(), None);
+   }
+
+   #[test]
+   fn nav_ops_change_the_stack() {
+      let mut t = TestTui::new();
+      let mut state = State::default();
+      let mut stack = ScreenStack::new(Named("root", false));
+      stack.enter(&mut state, t.tui());
+      run(&mut t, &mut stack, &mut state, Op::Push("a"));
+      run(&mut t, &mut stack, &mut state, Op::Replace("b"));
+      assert_eq!(stack.names(), vec!["root", "b"]);
+      run(&mut t, &mut stack, &mut state, Op::Pop);
+      assert_eq!(stack.top(), Some("root"));
+      run(&mut t, &mut stack, &mut state, Op::Push("c"));
+      run(&mut t, &mut stack, &mut state, Op::Reset("home"));
+      assert_eq!(stack.names(), vec!["home"]);
+      let log = [
+         "enter root", "enter a", "leave a", "enter b", "leave b", "enter root", "enter c",
+         "leave c", "leave root", "enter home",
+      ];
+      assert_eq!(state.log, log);
+   }
+
+   #[test]
+   fn popping_the_last_screen_exits() {
+      let mut t = TestTui::new();
+      let mut state = State::default();
+      let mut stack = ScreenStack::from_boxed(Box::new(Named("root", false)));
+      run(&mut t, &mut stack, &mut state, Op::Pop);
+      assert!(stack.is_empty());
+      assert!(!t.runtime.is_running());
+   }
+
+   #[test]
+   fn transitions_apply_to_every_op() {
+      let mut t = TestTui::new();
+      let mut state = State {
+         transition: Transition::new(TransitionKind::Wipe, Duration::from_secs(3600)),
+         ..Default::default()
+      };
+      let mut stack = ScreenStack::new(Named("root", false));
+      run(&mut t, &mut stack, &mut state, Op::Reset("home"));
+      assert!(stack.is_transitioning());
+      let active = stack.active.as_ref().unwrap();
+      assert_eq!(active.leaving.as_ref().map(|s| s.name()), Some("root"));
+      state.transition = Transition::default();
+      run(&mut t, &mut stack, &mut state, Op::Push("a"));
+      assert!(!stack.is_transitioning());
+   }
+
+   #[test]
+   fn overlays_render_over_the_screen_below() {
+      let mut t = TestTui::new();
+      let mut state = State::default();
+      let mut stack = ScreenStack::new(Named("root", false));
+      run(&mut t, &mut stack, &mut state, Op::Push("page"));
+      run(&mut t, &mut stack, &mut state, Op::Overlay("menu"));
+      let area = Rect::new(0, 0, 8, 2);
+      let mut buf = Buffer::empty(area);
+      stack.render(&state, t.tui_ref(), area, &mut buf);
+      assert_eq!(buf, Buffer::with_lines(["page    ", "menu    "]));
+   }
+}
@@ -0,0 +1,205 @@
+use crate::tui::{backup_cfg, unix_stamp};
+use crate::{app_err, AppOutput};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+#[derive(Debug, Default)]
+struct StoreInner {
+   path: Option<PathBuf>,
+   values: Map<String, Value>,
+   dirty: bool,
+}
+
+// cheap handle, clones share the same values (the lua api holds one too)
+#[derive(Debug, Clone, Default)]
+pub struct Store {
+   inner: Rc<RefCell<StoreInner>>,
+}
+
+impl Store {
+   // a store that can't be read is never saved over, a corrupt one is backed up first
+   pub(crate) fn load(mut path: Option<PathBuf>) -> (Self, Vec<String>) {
+      let mut warnings = Vec::new();
+      let src = match path.as_ref().map(std::fs::read_to_string) {
+         Some(Ok(src)) => Some(src),
+         Some(Err(e)) if e.kind() != ErrorKind::NotFound => {
+            warnings.push(format!("failed to read store at {:?}, not saving it: {}", path, e));
+            path = None;
+            None
+         }
+         _ => None,
+      };
+      let values = match (&path, src) {
+         (Some(p), Some(src)) => match serde_json::from_str::<Map<String, Value>>(&src) {
+            Ok(values) => values,
+            Err(e) => {
+               match backup_cfg(p, &format!("bad-{}", unix_stamp())) {
+                  AppOutput::Ok(backup) => warnings.push(format!(
+                     "store at {:?} is corrupt ({}), moved it to {:?}",
+                     p, e, backup
+                  )),
+                  _ => {
+                     warnings.push(format!("store at {:?} is corrupt ({}), not saving it", p, e));
+                     path = None;
+                  }
+               }
+               Map::new()
+            }
+         },
+         _ => Map::new(),
+      };
+      let store = Self {
+         inner: Rc::new(RefCell::new(StoreInner {
+            path,
+            values,
+            dirty: false,
+         })),
+      };
+      (store, warnings)
+   }
+
+   pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+      serde_json::from_value(self.get_value(key)?).ok()
+   }
+   pub fn get_value(&self, key: &str) -> Option<Value> {
+      self.inner.borrow().values.get(key).cloned()
+   }
+   pub fn set<T: Serialize>(&self, key: &str, value: T) {
+      if let Ok(v) = serde_json::to_value(value) {
+         self.set_value(key, v);
+      }
+   }
+   pub fn set_value(&self, key: &str, value: Value) {
+      let mut inner = self.inner.borrow_mut();
+      inner.values.insert(key.to_string(), value);
+      inner.dirty = true;
+   }
+   pub fn remove(&self, key: &str) {
+      let mut inner = self.inner.borrow_mut();
+      if inner.values.remove(key).is_some() {
+         inner.dirty = true;
+      }
+   }
+   pub fn contains(&self, key: &str) -> bool {
+      self.inner.borrow().values.contains_key(key)
+   }
+   pub fn keys(&self) -> Vec<String> {
+      self.inner.borrow().values.keys().cloned().collect()
+   }
+
+   pub(crate) fn save(&self) -> AppOutput<()> {
+      self.inner.borrow_mut().save()
+   }
+}
+
+impl StoreInner {
+   // writes to a temp file first so a crash mid-write never leaves a torn store
+   fn save(&mut self) -> AppOutput<()> {
+      let path = match (&self.path, self.dirty) {
+         (Some(p), true) => p.clone(),
+         _ => return AppOutput::void(),
+      };
+      let src = match serde_json::to_string_pretty(&self.values) {
+         Ok(src) => src,
+         Err(e) => return app_err!("failed to serialize store: {}", e),
+      };
+      let tmp = path.with_extension("json.tmp");
+      if let Err(e) = std::fs::write(&tmp, src) {
+         return app_err!("failed to write store at {:?}: {}", tmp, e);
+      }
+      if let Err(e) = std::fs::rename(&tmp, &path) {
+         return app_err!("failed to move store into place at {:?}: {}", path, e);
+      }
+      self.dirty = false;
+      AppOutput::void()
+   }
+}
+
+// the clean exit saves and reports errors, this catches panics and early returns
+impl Drop for StoreInner {
+   fn drop(&mut self) {
+      if let AppOutput::Err(e) = self.save() {
+         eprintln!("{e}");
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   // a fresh directory per test, removed again at the end
+   struct TempDir(PathBuf);
+
+   impl TempDir {
+      fn new(name: &str) -> Self {
+         let dir = std::env::temp_dir().join(format!("katatui-{name}-{}", std::process::id()));
+         let _ = std::fs::remove_dir_all(&dir);
+         std::fs::create_dir_all(&dir).unwrap();
+         TempDir(dir)
+      }
+   }
+
+   impl Drop for TempDir {
+      fn drop(&mut self) {
+         let _ = std::fs::remove_dir_all(&self.0);
+      }
+   }
+
+   #[test]
+   fn values_round_trip_through_the_file() {
+      let dir = TempDir::new("store-round-trip");
+      let path = dir.0.join("store.json");
+      let (store, warnings) = Store::load(Some(path.clone()));
+      assert!(warnings.is_empty() && store.keys().is_empty());
+      store.set("n", 3);
+      store.set("names", vec!["a", "b"]);
+      assert!(matches!(store.save(), AppOutput::Nil));
+      assert!(path.exists() && !path.with_extension("json.tmp").exists());
+
+      let (store, _) = Store::load(Some(path.clone()));
+      assert_eq!(store.get::<i32>("n"), Some(3));
+      assert_eq!(store.get::<Vec<String>>("names").unwrap(), vec!["a", "b"]);
+      store.remove("n");
+      drop(store);
+      let (store, _) = Store::load(Some(path));
+      assert!(!store.contains("n"));
+   }
+
+   #[test]
+   fn corrupt_stores_are_backed_up_before_saving() {
+      let dir = TempDir::new("store-corrupt");
+      let path = dir.0.join("store.json");
+      std::fs::write(&path, "{ not json").unwrap();
+      let (store, warnings) = Store::load(Some(path.clone()));
+      assert_eq!(warnings.len(), 1);
+      assert!(store.keys().is_empty());
+      let backup = std::fs::read_dir(&dir.0)
+         .unwrap()
+         .filter_map(|e| e.ok())
+         .find(|e| e.file_name().to_string_lossy().ends_with(".bak"))
+         .unwrap();
+      assert_eq!(std::fs::read_to_string(backup.path()).unwrap(), "{ not json");
+      store.set("n", 1);
+      drop(store);
+      assert!(std::fs::read_to_string(&path).unwrap().contains("\"n\""));
+   }
+
+   #[test]
+   fn unreadable_stores_are_left_alone() {
+      let dir = TempDir::new("store-unreadable");
+      // a directory where the file should be fails to read
+      let path = dir.0.join("store.json");
+      std::fs::create_dir(&path).unwrap();
+      let (store, warnings) = Store::load(Some(path.clone()));
+      assert_eq!(warnings.len(), 1);
+      store.set("n", 1);
+      assert!(matches!(store.save(), AppOutput::Nil));
+      assert!(path.is_dir());
+   }
+}
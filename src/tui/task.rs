@@ -0,0 +1,479 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, Once};
+use std::thread;
+use std::time::Duration;
+
+pub type TaskId = u64;
+pub type TaskOutput = Box<dyn Any + Send>;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+thread_local! {
+   static IN_TASK: Cell<bool> = const { Cell::new(false) };
+}
+
+// true on task pool threads, their panics are caught and reported as `TaskEvent::Failed`
+pub(crate) fn in_task() -> bool {
+   IN_TASK.with(|flag| flag.get())
+}
+
+// the hooks installed by ratatui and the capture restore the terminal and stdio, which
+// a panic that's going to be caught must not do
+fn install_panic_hook() {
+   static INSTALL: Once = Once::new();
+   INSTALL.call_once(|| {
+      let prev = panic::take_hook();
+      panic::set_hook(Box::new(move |info| {
+         if !in_task() {
+            prev(info);
+         }
+      }));
+   });
+}
+
+fn panic_msg(payload: &(dyn Any + Send)) -> String {
+   match payload.downcast_ref::<&str>() {
+      Some(msg) => msg.to_string(),
+      None => payload
+         .downcast_ref::<String>()
+         .cloned()
+         .unwrap_or_else(|| "unknown panic".to_string()),
+   }
+}
+
+#[derive(Debug)]
+pub enum TaskEvent {
+   Progress {
+      id: TaskId,
+      progress: f32,
+      msg: Option<String>,
+   },
+   Done {
+      id: TaskId,
+      result: TaskOutput,
+   },
+   Failed {
+      id: TaskId,
+      error: String,
+   },
+   Cancelled {
+      id: TaskId,
+   },
+}
+
+impl TaskEvent {
+   pub fn id(&self) -> TaskId {
+      match self {
+         TaskEvent::Progress { id, .. }
+         | TaskEvent::Done { id, .. }
+         | TaskEvent::Failed { id, .. }
+         | TaskEvent::Cancelled { id } => *id,
+      }
+   }
+   pub fn is_finished(&self) -> bool {
+      !matches!(self, TaskEvent::Progress { .. })
+   }
+   pub fn result<T: 'static>(&self) -> Option<&T> {
+      match self {
+         TaskEvent::Done { result, .. } => result.downcast_ref::<T>(),
+         _ => None,
+      }
+   }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessOutput {
+   pub status: Option<i32>,
+   pub stdout: String,
+   pub stderr: String,
+}
+
+impl ProcessOutput {
+   pub fn success(&self) -> bool {
+      self.status == Some(0)
+   }
+}
+
+// handed to every task, long tasks should check `is_cancelled` between steps
+#[derive(Debug, Clone)]
+pub struct TaskCtx {
+   id: TaskId,
+   cancel: Arc<AtomicBool>,
+   events: Sender<TaskEvent>,
+}
+
+impl TaskCtx {
+   pub fn id(&self) -> TaskId {
+      self.id
+   }
+   pub fn is_cancelled(&self) -> bool {
+      self.cancel.load(Ordering::Relaxed)
+   }
+   pub fn progress(&self, progress: f32) {
+      let _ = self.events.send(TaskEvent::Progress {
+         id: self.id,
+         progress: progress.clamp(0.0, 1.0),
+         msg: None,
+      });
+   }
+   pub fn progress_msg(&self, progress: f32, msg: &str) {
+      let _ = self.events.send(TaskEvent::Progress {
+         id: self.id,
+         progress: progress.clamp(0.0, 1.0),
+         msg: Some(msg.to_string()),
+      });
+   }
+}
+
+#[derive(Debug)]
+struct TaskInfo {
+   name: String,
+   cancel: Arc<AtomicBool>,
+   progress: f32,
+}
+
+// a small thread pool, results come back as events drained once per tick
+#[derive(Debug)]
+pub struct Tasks {
+   jobs: Option<Sender<Job>>,
+   events_tx: Sender<TaskEvent>,
+   events_rx: Receiver<TaskEvent>,
+   running: HashMap<TaskId, TaskInfo>,
+   events: Vec<TaskEvent>,
+   next_id: TaskId,
+}
+
+impl Tasks {
+   pub(crate) fn new(workers: usize) -> Self {
+      install_panic_hook();
+      let (jobs_tx, jobs_rx) = mpsc::channel::<Job>();
+      let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+      for i in 0..workers.max(1) {
+         let jobs_rx = jobs_rx.clone();
+         let _ = thread::Builder::new()
+            .name(format!("katatui-task-{i}"))
+            .spawn(move || {
+               IN_TASK.with(|flag| flag.set(true));
+               loop {
+                  let job = match jobs_rx.lock() {
+                     Ok(rx) => rx.recv(),
+                     Err(_) => return,
+                  };
+                  match job {
+                     Ok(job) => job(),
+                     Err(_) => return,
+                  }
+               }
+            });
+      }
+      let (events_tx, events_rx) = mpsc::channel();
+      Self {
+         jobs: Some(jobs_tx),
+         events_tx,
+         events_rx,
+         running: HashMap::new(),
+         events: Vec::new(),
+         next_id: 0,
+      }
+   }
+
+   pub fn spawn<T, F>(&mut self, name: &str, f: F) -> TaskId
+   where
+      T: Send + 'static,
+      F: FnOnce(&TaskCtx) -> Result<T, String> + Send + 'static,
+   {
+      let (id, job) = self.start(name, f);
+      if let Some(jobs) = &self.jobs {
+         let _ = jobs.send(job);
+      }
+      id
+   }
+
+   // registers the task and wraps `f` so whatever runs it reports back as an event
+   fn start<T, F>(&mut self, name: &str, f: F) -> (TaskId, Job)
+   where
+      T: Send + 'static,
+      F: FnOnce(&TaskCtx) -> Result<T, String> + Send + 'static,
+   {
+      self.next_id += 1;
+      let id = self.next_id;
+      let cancel = Arc::new(AtomicBool::new(false));
+      self.running.insert(
+         id,
+         TaskInfo {
+            name: name.to_string(),
+            cancel: cancel.clone(),
+            progress: 0.0,
+         },
+      );
+      let ctx = TaskCtx {
+         id,
+         cancel,
+         events: self.events_tx.clone(),
+      };
+      let job = move || {
+         let result = panic::catch_unwind(AssertUnwindSafe(|| f(&ctx)));
+         let event = match result {
+            _ if ctx.is_cancelled() => TaskEvent::Cancelled { id },
+            Ok(Ok(value)) => TaskEvent::Done {
+               id,
+               result: Box::new(value),
+            },
+            Ok(Err(error)) => TaskEvent::Failed { id, error },
+            Err(payload) => TaskEvent::Failed {
+               id,
+               error: format!("task panicked: {}", panic_msg(payload.as_ref())),
+            },
+         };
+         let _ = ctx.events.send(event);
+      };
+      (id, Box::new(job))
+   }
+
+   // runs `cmd` to completion, the result is a `ProcessOutput`. cancelling kills it.
+   // waiting happens on a thread of its own so long processes don't hold up the pool
+   pub fn spawn_process(&mut self, name: &str, mut cmd: Command) -> TaskId {
+      let (id, job) = self.start(name, move |ctx| {
+         let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to spawn process: {e}"))?;
+
+         // read both pipes on their own threads so a full pipe can't stall the child
+         let read = |pipe: Option<Box<dyn Read + Send>>| {
+            thread::spawn(move || {
+               let mut out = String::new();
+               if let Some(mut pipe) = pipe {
+                  let _ = pipe.read_to_string(&mut out);
+               }
+               out
+            })
+         };
+         let stdout = read(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+         let stderr = read(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+
+         let status = loop {
+            if ctx.is_cancelled() {
+               let _ = child.kill();
+               let _ = child.wait();
+               return Err("cancelled".to_string());
+            }
+            match child.try_wait() {
+               Ok(Some(status)) => break status,
+               Ok(None) => thread::sleep(Duration::from_millis(10)),
+               Err(e) => return Err(format!("failed to wait on process: {e}")),
+            }
+         };
+         Ok(ProcessOutput {
+            status: status.code(),
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+         })
+      });
+      let spawned = thread::Builder::new()
+         .name(format!("katatui-process-{id}"))
+         .spawn(move || {
+            IN_TASK.with(|flag| flag.set(true));
+            job()
+         });
+      if let Err(e) = spawned {
+         let error = format!("failed to start a thread for the process: {e}");
+         let _ = self.events_tx.send(TaskEvent::Failed { id, error });
+      }
+      id
+   }
+
+   pub fn cancel(&mut self, id: TaskId) -> bool {
+      match self.running.get(&id) {
+         Some(task) => {
+            task.cancel.store(true, Ordering::Relaxed);
+            true
+         }
+         None => false,
+      }
+   }
+   pub fn cancel_all(&mut self) {
+      for task in self.running.values() {
+         task.cancel.store(true, Ordering::Relaxed);
+      }
+   }
+
+   pub fn is_running(&self, id: TaskId) -> bool {
+      self.running.contains_key(&id)
+   }
+   pub fn running(&self) -> usize {
+      self.running.len()
+   }
+   pub fn name(&self, id: TaskId) -> Option<&str> {
+      self.running.get(&id).map(|t| t.name.as_str())
+   }
+   pub fn progress(&self, id: TaskId) -> Option<f32> {
+      self.running.get(&id).map(|t| t.progress)
+   }
+
+   // events received on the current tick
+   pub fn events(&self) -> &[TaskEvent] {
+      &self.events
+   }
+   pub fn take_events(&mut self) -> Vec<TaskEvent> {
+      std::mem::take(&mut self.events)
+   }
+
+   pub(crate) fn drain(&mut self) {
+      self.events = self.events_rx.try_iter().collect();
+      for event in &self.events {
+         match event {
+            TaskEvent::Progress { id, progress, .. } => {
+               if let Some(task) = self.running.get_mut(id) {
+                  task.progress = *progress;
+               }
+            }
+            event => {
+               self.running.remove(&event.id());
+            }
+         }
+      }
+   }
+}
+
+impl Drop for Tasks {
+   // workers exit once the queue closes, running tasks are asked to stop but not waited on
+   fn drop(&mut self) {
+      self.cancel_all();
+      self.jobs.take();
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use std::time::Instant;
+
+   // drains until every task in `ids` finished, their final events in the same order
+   fn finish(tasks: &mut Tasks, ids: &[TaskId]) -> Vec<TaskEvent> {
+      let start = Instant::now();
+      let mut done: Vec<Option<TaskEvent>> = ids.iter().map(|_| None).collect();
+      while start.elapsed() < Duration::from_secs(5) {
+         tasks.drain();
+         for event in tasks.take_events().into_iter().filter(|e| e.is_finished()) {
+            if let Some(i) = ids.iter().position(|id| *id == event.id()) {
+               done[i] = Some(event);
+            }
+         }
+         if done.iter().all(Option::is_some) {
+            return done.into_iter().flatten().collect();
+         }
+         thread::sleep(Duration::from_millis(5));
+      }
+      panic!("tasks {ids:?} didn't finish");
+   }
+   fn last(tasks: &mut Tasks, id: TaskId) -> TaskEvent {
+      finish(tasks, &[id]).remove(0)
+   }
+
+   #[test]
+   fn results_and_errors_become_events() {
+      let mut tasks = Tasks::new(2);
+      let done = tasks.spawn("done", |_| Ok(41 + 1));
+      let failed = tasks.spawn::<(), _>("failed", |_| Err("nope".to_string()));
+      assert_eq!(tasks.name(done), Some("done"));
+      let events = finish(&mut tasks, &[done, failed]);
+      assert_eq!(events[0].result::<i32>(), Some(&42));
+      match &events[1] {
+         TaskEvent::Failed { error, .. } => assert_eq!(error, "nope"),
+         other => panic!("expected a failure, got {other:?}"),
+      }
+      assert_eq!(tasks.running(), 0);
+   }
+
+   #[test]
+   fn panics_are_caught() {
+      let mut tasks = Tasks::new(1);
+      let id = tasks.spawn::<(), _>("panics", |_| panic!("boom"));
+      match last(&mut tasks, id) {
+         TaskEvent::Failed { error, .. } => assert_eq!(error, "task panicked: boom"),
+         other => panic!("expected a failure, got {other:?}"),
+      }
+      // the worker survives the panic
+      let id = tasks.spawn("after", |_| Ok(()));
+      assert!(matches!(last(&mut tasks, id), TaskEvent::Done { .. }));
+   }
+
+   #[test]
+   fn cancelled_tasks_report_cancelled() {
+      let mut tasks = Tasks::new(1);
+      let id = tasks.spawn("loops", |ctx| {
+         while !ctx.is_cancelled() {
+            thread::sleep(Duration::from_millis(1));
+         }
+         Ok(())
+      });
+      assert!(tasks.cancel(id));
+      assert!(matches!(last(&mut tasks, id), TaskEvent::Cancelled { .. }));
+      assert!(!tasks.cancel(id));
+   }
+
+   #[test]
+   fn progress_is_kept_while_running() {
+      let mut tasks = Tasks::new(1);
+      let (go_tx, go_rx) = mpsc::channel::<()>();
+      let id = tasks.spawn("steps", move |ctx| {
+         ctx.progress_msg(1.5, "half");
+         let _ = go_rx.recv();
+         Ok(())
+      });
+      let start = Instant::now();
+      while tasks.progress(id) == Some(0.0) && start.elapsed() < Duration::from_secs(5) {
+         tasks.drain();
+         thread::sleep(Duration::from_millis(5));
+      }
+      assert_eq!(tasks.progress(id), Some(1.0));
+      assert!(tasks.is_running(id));
+      go_tx.send(()).unwrap();
+      last(&mut tasks, id);
+      assert_eq!(tasks.progress(id), None);
+   }
+
+   #[cfg(unix)]
+   #[test]
+   fn processes_report_their_output() {
+      let mut tasks = Tasks::new(1);
+      let mut cmd = Command::new("sh");
+      cmd.args(["-c", "echo out; echo err >&2; exit 3"]);
+      let id = tasks.spawn_process("sh", cmd);
+      let out = last(&mut tasks, id);
+      let out = out.result::<ProcessOutput>().unwrap();
+      assert_eq!(out.status, Some(3));
+      assert_eq!((out.stdout.as_str(), out.stderr.as_str()), ("out\n", "err\n"));
+   }
+
+   #[cfg(unix)]
+   #[test]
+   fn long_processes_leave_the_pool_free_and_die_on_cancel() {
+      let mut tasks = Tasks::new(1);
+      let sleeps: Vec<_> = (0..4)
+         .map(|_| {
+            let mut cmd = Command::new("sleep");
+            cmd.arg("30");
+            tasks.spawn_process("sleep", cmd)
+         })
+         .collect();
+      let quick = tasks.spawn("quick", |_| Ok(()));
+      assert!(matches!(last(&mut tasks, quick), TaskEvent::Done { .. }));
+      let start = Instant::now();
+      for id in sleeps {
+         tasks.cancel(id);
+         assert!(matches!(last(&mut tasks, id), TaskEvent::Cancelled { .. }));
+      }
+      assert!(start.elapsed() < Duration::from_secs(5));
+   }
+}
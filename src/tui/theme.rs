@@ -0,0 +1,238 @@
+use crate::{parse_color, rgb_to_color, BarColors, LuaValue};
+use kolor::RGB;
+use ratatui::prelude::Color;
+use ratatui::style::{Modifier, Style};
+use std::collections::HashMap;
+
+pub const THEME_SLOTS: [&str; 12] = [
+   "fg",
+   "bg",
+   "muted",
+   "accent",
+   "accent_alt",
+   "border",
+   "selection",
+   "focus",
+   "info",
+   "success",
+   "warn",
+   "error",
+];
+
+pub const THEME_PRESETS: [&str; 4] = ["dark", "light", "gruvbox", "nord"];
+
+// slot values in THEME_SLOTS order
+fn preset_hex(name: &str) -> Option<[u32; 12]> {
+   match name {
+      "dark" => Some([
+         0xd8dee9, 0x1e1f29, 0x6c7086, 0xc678dd, 0xf0a6ff, 0x5c6370, 0x3e4452, 0x61afef, 0x61afef,
+         0x98c379, 0xe5c07b, 0xe06c75,
+      ]),
+      "light" => Some([
+         0x383a42, 0xfafafa, 0xa0a1a7, 0xa626a4, 0xd88fd6, 0xc8c8c8, 0xe5e5e6, 0x4078f2, 0x4078f2,
+         0x50a14f, 0xc18401, 0xe45649,
+      ]),
+      "gruvbox" => Some([
+         0xebdbb2, 0x282828, 0x928374, 0xd3869b, 0xfe8019, 0x504945, 0x3c3836, 0x83a598, 0x83a598,
+         0xb8bb26, 0xfabd2f, 0xfb4934,
+      ]),
+      "nord" => Some([
+         0xeceff4, 0x2e3440, 0x4c566a, 0xb48ead, 0x88c0d0, 0x434c5e, 0x3b4252, 0x88c0d0, 0x81a1c1,
+         0xa3be8c, 0xebcb8b, 0xbf616a,
+      ]),
+      _ => None,
+   }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+   name: String,
+   slots: HashMap<String, Color>,
+}
+
+impl Default for Theme {
+   fn default() -> Self {
+      Theme::dark()
+   }
+}
+
+impl Theme {
+   pub fn preset(name: &str) -> Option<Self> {
+      let hex = preset_hex(name)?;
+      let slots = THEME_SLOTS
+         .iter()
+         .zip(hex)
+         .map(|(slot, v)| {
+            let color = Color::Rgb((v >> 16) as u8, (v >> 8) as u8, v as u8);
+            (slot.to_string(), color)
+         })
+         .collect();
+      Some(Self {
+         name: name.to_string(),
+         slots,
+      })
+   }
+   pub fn dark() -> Self {
+      Theme::preset("dark").unwrap_or_else(|| Theme {
+         name: "dark".to_string(),
+         slots: HashMap::new(),
+      })
+   }
+   pub fn light() -> Self {
+      Theme::preset("light").unwrap_or_default()
+   }
+
+   pub fn name(&self) -> &str {
+      &self.name
+   }
+   pub fn get(&self, slot: &str) -> Option<Color> {
+      self.slots.get(slot).copied()
+   }
+   // unknown slots fall back to the terminal's own colors
+   pub fn color(&self, slot: &str) -> Color {
+      self.get(slot).unwrap_or(Color::Reset)
+   }
+   pub fn set(&mut self, slot: &str, color: Color) {
+      self.slots.insert(slot.to_string(), color);
+   }
+   pub fn set_rgb(&mut self, slot: &str, rgb: RGB) {
+      self.set(slot, rgb_to_color(rgb));
+   }
+   pub fn slots(&self) -> impl Iterator<Item = (&str, Color)> {
+      self.slots.iter().map(|(k, v)| (k.as_str(), *v))
+   }
+
+   pub fn style(&self, slot: &str) -> Style {
+      Style::default().fg(self.color(slot))
+   }
+   pub fn style_bg(&self, slot: &str) -> Style {
+      Style::default().bg(self.color(slot))
+   }
+   pub fn style_fg_bg(&self, fg: &str, bg: &str) -> Style {
+      Style::default().fg(self.color(fg)).bg(self.color(bg))
+   }
+   pub fn bold(&self, slot: &str) -> Style {
+      self.style(slot).add_modifier(Modifier::BOLD)
+   }
+
+   pub fn bar_colors(&self) -> BarColors {
+      BarColors {
+         text: self.color("bg"),
+         base: self.color("accent_alt"),
+         app: self.color("accent"),
+         info: self.color("fg"),
+         cfg: self.color("fg"),
+         func: self.color("accent"),
+      }
+   }
+
+   // `theme = "nord"` or `theme = { preset = "nord", accent = "#ff8800" }`,
+   // returns a warning per bad value
+   pub(crate) fn apply_cfg(&mut self, value: &LuaValue) -> Vec<String> {
+      let mut warnings = Vec::new();
+      let table = match value {
+         LuaValue::String(s) => {
+            self.apply_preset(&s.to_string_lossy(), &mut warnings);
+            return warnings;
+         }
+         LuaValue::Table(t) => t,
+         LuaValue::Nil => return warnings,
+         other => {
+            warnings.push(format!(
+               "theme should be a preset name or a table, got {}",
+               other.type_name()
+            ));
+            return warnings;
+         }
+      };
+      if let Ok(Some(preset)) = table.get::<Option<String>>("preset") {
+         self.apply_preset(&preset, &mut warnings);
+      }
+      for pair in table.pairs::<String, LuaValue>() {
+         let (slot, value) = match pair {
+            Ok(pair) => pair,
+            Err(e) => {
+               warnings.push(format!("theme: {e}"));
+               continue;
+            }
+         };
+         if slot == "preset" {
+            continue;
+         }
+         match value {
+            LuaValue::String(s) => {
+               let s = s.to_string_lossy();
+               match parse_color(&s) {
+                  Ok(color) => self.set(&slot, color),
+                  Err(e) => warnings.push(format!("theme.{slot}: {e}")),
+               }
+            }
+            other => warnings.push(format!(
+               "theme.{slot}: expected a color string, got {}",
+               other.type_name()
+            )),
+         }
+      }
+      warnings
+   }
+
+   fn apply_preset(&mut self, name: &str, warnings: &mut Vec<String>) {
+      match Theme::preset(name) {
+         Some(theme) => *self = theme,
+         None => warnings.push(format!(
+            "theme: unknown preset {name:?}, expected one of {}",
+            THEME_PRESETS.join(", ")
+         )),
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use mlua::Lua;
+
+   fn apply(theme: &mut Theme, src: &str) -> Vec<String> {
+      let lua = Lua::new();
+      theme.apply_cfg(&lua.load(src).eval::<LuaValue>().unwrap())
+   }
+
+   #[test]
+   fn presets_fill_every_slot() {
+      for name in THEME_PRESETS {
+         let theme = Theme::preset(name).unwrap();
+         assert!(THEME_SLOTS.iter().all(|slot| theme.get(slot).is_some()), "{name}");
+      }
+      assert!(Theme::preset("nope").is_none());
+   }
+
+   #[test]
+   fn cfg_overrides_only_the_keys_it_sets() {
+      let mut theme = Theme::dark();
+      theme.set("custom", Color::Red);
+      let warnings = apply(&mut theme, "return { accent = '#f80', muted = 'blue' }");
+      assert!(warnings.is_empty());
+      assert_eq!(theme.get("accent"), Some(Color::Rgb(0xff, 0x88, 0x00)));
+      assert_eq!(theme.get("muted"), Some(Color::Blue));
+      assert_eq!(theme.get("custom"), Some(Color::Red));
+      assert_eq!(theme.get("fg"), Theme::dark().get("fg"));
+   }
+
+   #[test]
+   fn cfg_preset_comes_before_overrides() {
+      let mut theme = Theme::dark();
+      apply(&mut theme, "return { preset = 'nord', fg = '#000' }");
+      assert_eq!(theme.name(), "nord");
+      assert_eq!(theme.get("fg"), Some(Color::Rgb(0, 0, 0)));
+      assert_eq!(theme.get("bg"), Theme::preset("nord").unwrap().get("bg"));
+   }
+
+   #[test]
+   fn bad_values_become_warnings() {
+      let mut theme = Theme::dark();
+      assert_eq!(apply(&mut theme, "return { accent = '#12', fg = 3 }").len(), 2);
+      assert_eq!(apply(&mut theme, "return 'nope'").len(), 1);
+      assert_eq!(apply(&mut theme, "return 42").len(), 1);
+      assert_eq!(theme, Theme::dark());
+   }
+}
@@ -0,0 +1,225 @@
+use crate::{LuaError, LuaFunction, LuaResult};
+use mlua::{Lua, RegistryKey};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+#[derive(Debug)]
+struct Timer {
+   id: String,
+   due: Duration,
+   every: Option<Duration>,
+   // lua timers carry their callback, rust timers only report their id
+   callback: Option<RegistryKey>,
+}
+
+#[derive(Debug, Default)]
+struct TimersInner {
+   now: Duration,
+   timers: Vec<Timer>,
+   fired: Vec<String>,
+   next_lua_id: u64,
+}
+
+impl TimersInner {
+   fn schedule(
+      &mut self,
+      id: &str,
+      delay: Duration,
+      every: Option<Duration>,
+      callback: Option<RegistryKey>,
+   ) {
+      self.timers.retain(|t| t.id != id);
+      self.timers.push(Timer {
+         id: id.to_string(),
+         due: self.now + delay,
+         every,
+         callback,
+      });
+   }
+   fn lua_id(&mut self) -> String {
+      self.next_lua_id += 1;
+      format!("lua:{}", self.next_lua_id)
+   }
+}
+
+// timers measured from runtime start rather than counted in ticks, so changing tps doesn't
+// change when they fire. a cheap handle shared with lua
+#[derive(Debug, Clone, Default)]
+pub struct Timers {
+   inner: Rc<RefCell<TimersInner>>,
+}
+
+impl Timers {
+   pub(crate) fn new() -> Self {
+      Self::default()
+   }
+
+   // scheduling an id that's already pending replaces it
+   pub fn after(&self, delay: Duration, id: &str) {
+      self.inner.borrow_mut().schedule(id, delay, None, None);
+   }
+   pub fn every(&self, interval: Duration, id: &str) {
+      self.inner.borrow_mut().schedule(id, interval, Some(interval), None);
+   }
+   pub fn cancel(&self, id: &str) -> bool {
+      let mut inner = self.inner.borrow_mut();
+      let before = inner.timers.len();
+      inner.timers.retain(|t| t.id != id);
+      inner.timers.len() != before
+   }
+   pub fn is_pending(&self, id: &str) -> bool {
+      self.inner.borrow().timers.iter().any(|t| t.id == id)
+   }
+   // time until `id` fires next
+   pub fn remaining(&self, id: &str) -> Option<Duration> {
+      let inner = self.inner.borrow();
+      let timer = inner.timers.iter().find(|t| t.id == id)?;
+      Some(timer.due.saturating_sub(inner.now))
+   }
+
+   // ids that fired on the current tick
+   pub fn fired(&self) -> Vec<String> {
+      self.inner.borrow().fired.clone()
+   }
+   pub fn has_fired(&self, id: &str) -> bool {
+      self.inner.borrow().fired.iter().any(|f| f == id)
+   }
+
+   pub(crate) fn after_lua(&self, lua: &Lua, delay: Duration, f: LuaFunction) -> LuaResult<String> {
+      let key = lua.create_registry_value(f)?;
+      let mut inner = self.inner.borrow_mut();
+      let id = inner.lua_id();
+      inner.schedule(&id, delay, None, Some(key));
+      Ok(id)
+   }
+   pub(crate) fn every_lua(
+      &self,
+      lua: &Lua,
+      interval: Duration,
+      f: LuaFunction,
+   ) -> LuaResult<String> {
+      let key = lua.create_registry_value(f)?;
+      let mut inner = self.inner.borrow_mut();
+      let id = inner.lua_id();
+      inner.schedule(&id, interval, Some(interval), Some(key));
+      Ok(id)
+   }
+   // the cfg registers its timers again when it's reloaded
+   pub(crate) fn clear_lua(&self) {
+      self.inner.borrow_mut().timers.retain(|t| t.callback.is_none());
+   }
+
+   // fires everything due by `now` and hands back the lua callbacks to run. a repeating timer
+   // that fell behind fires once and skips the missed intervals
+   pub(crate) fn advance(&self, now: Duration, lua: Option<&Lua>) -> Vec<(String, LuaFunction)> {
+      let mut inner = self.inner.borrow_mut();
+      inner.now = now;
+      inner.fired.clear();
+
+      let mut fired = Vec::new();
+      let mut callbacks = Vec::new();
+      inner.timers.retain_mut(|timer| {
+         if timer.due > now {
+            return true;
+         }
+         fired.push(timer.id.clone());
+         if let (Some(lua), Some(key)) = (lua, &timer.callback)
+            && let Ok(f) = lua.registry_value::<LuaFunction>(key)
+         {
+            callbacks.push((timer.id.clone(), f));
+         }
+         match timer.every {
+            Some(interval) => {
+               timer.due += interval.max(Duration::from_millis(1));
+               if timer.due <= now {
+                  timer.due = now + interval;
+               }
+               true
+            }
+            None => false,
+         }
+      });
+      inner.fired = fired;
+      callbacks
+   }
+}
+
+// seconds from lua, where inf, nan and negative numbers are all easy to pass by accident
+pub(crate) fn lua_secs(secs: f64) -> LuaResult<Duration> {
+   Duration::try_from_secs_f64(secs)
+      .map_err(|e| LuaError::runtime(format!("invalid duration {secs} seconds: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn ms(n: u64) -> Duration {
+      Duration::from_millis(n)
+   }
+
+   #[test]
+   fn one_shot_fires_once() {
+      let timers = Timers::new();
+      timers.after(ms(100), "t");
+      timers.advance(ms(50), None);
+      assert!(!timers.has_fired("t"));
+      assert_eq!(timers.remaining("t"), Some(ms(50)));
+      timers.advance(ms(100), None);
+      assert_eq!(timers.fired(), vec!["t".to_string()]);
+      assert!(!timers.is_pending("t"));
+      timers.advance(ms(200), None);
+      assert!(timers.fired().is_empty());
+   }
+
+   #[test]
+   fn repeating_timers_skip_missed_intervals() {
+      let timers = Timers::new();
+      timers.every(ms(100), "r");
+      timers.advance(ms(100), None);
+      assert!(timers.has_fired("r"));
+      timers.advance(ms(150), None);
+      assert!(!timers.has_fired("r"));
+      timers.advance(ms(1000), None);
+      assert!(timers.has_fired("r"));
+      assert_eq!(timers.remaining("r"), Some(ms(100)));
+   }
+
+   #[test]
+   fn rescheduling_replaces_and_cancel_removes() {
+      let timers = Timers::new();
+      timers.after(ms(100), "t");
+      timers.after(ms(300), "t");
+      timers.advance(ms(100), None);
+      assert!(!timers.has_fired("t"));
+      assert!(timers.cancel("t"));
+      assert!(!timers.cancel("t"));
+      timers.advance(ms(300), None);
+      assert!(!timers.has_fired("t"));
+   }
+
+   #[test]
+   fn lua_callbacks_are_handed_back_and_cleared_on_reload() {
+      let lua = Lua::new();
+      let timers = Timers::new();
+      let f: LuaFunction = lua.load("function() end").eval().unwrap();
+      let once = timers.after_lua(&lua, ms(10), f.clone()).unwrap();
+      let every = timers.every_lua(&lua, ms(10), f).unwrap();
+      timers.every(ms(10), "rust");
+      let ids: Vec<_> = timers.advance(ms(10), Some(&lua)).into_iter().map(|(id, _)| id).collect();
+      assert_eq!(ids, vec![once.clone(), every.clone()]);
+      assert!(!timers.is_pending(&once));
+      timers.clear_lua();
+      assert!(!timers.is_pending(&every));
+      assert!(timers.is_pending("rust"));
+   }
+
+   #[test]
+   fn lua_secs_rejects_bad_numbers() {
+      assert_eq!(lua_secs(0.5).unwrap(), ms(500));
+      for secs in [-1.0, f64::NAN, f64::INFINITY] {
+         assert!(lua_secs(secs).is_err());
+      }
+   }
+}
@@ -1,21 +1,40 @@
 use crate::app_err;
-use crate::tui::{install_cfg, read_cfg, Cfg, CfgSrc};
-use crate::{App, AppOutput, Debug, Runtime};
-use mlua::Lua;
-use ratatui::crossterm::event;
+use crate::tui::debug::Debug;
+use crate::tui::{
+   cfg_as, cfg_get, cfg_table, eval_cfg, install_cfg, migrate_cfg, read_cfg, Cfg, CfgFormat,
+   CfgSrc,
+};
+use crate::tui::{
+   call_lua_fn, call_lua_func, install_tracing, lua_fn_name, markup, render_perf, take_color_flag,
+   Capture, CaptureStream, ConsoleInput, LuaApi,
+};
+#[cfg(feature = "async")]
+use crate::{Async, AsyncApp, AsyncCtx, Spawner};
+use crate::{
+   Animator, App, AppDirs, AppOutput, BarColors, CliAction, LogSource, LuaTable, LuaValue,
+   MarkupError, Runtime, Store, TaskCtx, TaskEvent, TaskId, Tasks, Theme, Timers,
+};
+use mlua::{FromLua, FromLuaMulti, IntoLuaMulti, Lua};
+use ratatui::crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event};
+use ratatui::crossterm::execute;
 use ratatui::prelude::*;
-use ratatui::widgets::{Block, Paragraph};
-use ratatui::{DefaultTerminal, Frame};
+use ratatui::{Frame, Terminal};
+use serde::de::DeserializeOwned;
 use std::env;
+use std::io::{self, Write};
+use std::process::Command;
 use std::time::{Duration, Instant};
-use unicode_width::UnicodeWidthStr;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct TUIRef<'a> {
    pub runtime: &'a Runtime,
    pub debug: &'a Debug,
    pub cfg: &'a Cfg,
    pub args: &'a Vec<String>,
+   pub dirs: &'a AppDirs,
+   pub store: &'a Store,
+   pub anim: &'a Animator,
+   pub theme: &'a Theme,
 }
 
 impl<'a> TUIRef<'a> {
@@ -24,14 +43,33 @@ impl<'a> TUIRef<'a> {
       debug: &'a Debug,
       cfg: &'a Cfg,
       args: &'a Vec<String>,
+      dirs: &'a AppDirs,
+      services: &'a Services,
    ) -> TUIRef<'a> {
       TUIRef {
          runtime,
          debug,
          cfg,
          args,
+         dirs,
+         store: &services.store,
+         anim: &services.anim,
+         theme: &services.theme,
       }
    }
+
+   pub fn cfg_table(&self) -> Option<LuaTable> {
+      cfg_table(self.cfg)
+   }
+   pub fn cfg_get<V: FromLua>(&self, path: &str) -> Option<V> {
+      cfg_get(self.cfg, path)
+   }
+   pub fn cfg_as<T: DeserializeOwned>(&self) -> Option<T> {
+      cfg_as(self.cfg)
+   }
+   pub fn markup(&self, src: &str) -> Result<Text<'static>, MarkupError> {
+      markup(src, self.theme)
+   }
 }
 
 #[derive(Debug)]
@@ -40,6 +78,12 @@ pub struct TUIMutRef<'a> {
    pub debug: &'a mut Debug,
    pub cfg: &'a mut Cfg,
    pub args: &'a mut Vec<String>,
+   pub dirs: &'a mut AppDirs,
+   pub store: &'a mut Store,
+   pub anim: &'a mut Animator,
+   pub timers: &'a mut Timers,
+   pub tasks: &'a mut Tasks,
+   pub theme: &'a mut Theme,
 }
 impl<'a> TUIMutRef<'a> {
    pub(crate) fn from(
@@ -47,28 +91,132 @@ impl<'a> TUIMutRef<'a> {
       debug: &'a mut Debug,
       cfg: &'a mut Cfg,
       args: &'a mut Vec<String>,
+      dirs: &'a mut AppDirs,
+      services: &'a mut Services,
    ) -> TUIMutRef<'a> {
       TUIMutRef {
          runtime,
          debug,
          cfg,
          args,
+         dirs,
+         store: &mut services.store,
+         anim: &mut services.anim,
+         timers: &mut services.timers,
+         tasks: &mut services.tasks,
+         theme: &mut services.theme,
+      }
+   }
+
+   // a shorter lived copy, for handing the refs to something else without giving them up
+   pub fn reborrow(&mut self) -> TUIMutRef<'_> {
+      TUIMutRef {
+         runtime: &mut *self.runtime,
+         debug: &mut *self.debug,
+         cfg: &mut *self.cfg,
+         args: &mut *self.args,
+         dirs: &mut *self.dirs,
+         store: &mut *self.store,
+         anim: &mut *self.anim,
+         timers: &mut *self.timers,
+         tasks: &mut *self.tasks,
+         theme: &mut *self.theme,
+      }
+   }
+   pub fn cfg_table(&self) -> Option<LuaTable> {
+      cfg_table(self.cfg)
+   }
+   pub fn cfg_get<V: FromLua>(&self, path: &str) -> Option<V> {
+      cfg_get(self.cfg, path)
+   }
+   pub fn cfg_as<T: DeserializeOwned>(&self) -> Option<T> {
+      cfg_as(self.cfg)
+   }
+   pub fn call_lua<R: FromLuaMulti>(
+      &mut self,
+      func: &str,
+      args: impl IntoLuaMulti,
+   ) -> AppOutput<R> {
+      call_lua_fn(self.cfg, self.debug, func, args)
+   }
+   pub fn after(&mut self, delay: Duration, id: &str) {
+      self.timers.after(delay, id);
+   }
+   pub fn every(&mut self, interval: Duration, id: &str) {
+      self.timers.every(interval, id);
+   }
+   pub fn cancel(&mut self, id: &str) -> bool {
+      self.timers.cancel(id)
+   }
+   pub fn timer_fired(&self, id: &str) -> bool {
+      self.timers.has_fired(id)
+   }
+   pub fn fired_timers(&self) -> Vec<String> {
+      self.timers.fired()
+   }
+   pub fn spawn<T, F>(&mut self, name: &str, f: F) -> TaskId
+   where
+      T: Send + 'static,
+      F: FnOnce(&TaskCtx) -> Result<T, String> + Send + 'static,
+   {
+      self.tasks.spawn(name, f)
+   }
+   pub fn spawn_process(&mut self, name: &str, cmd: Command) -> TaskId {
+      self.tasks.spawn_process(name, cmd)
+   }
+   pub fn cancel_task(&mut self, id: TaskId) -> bool {
+      self.tasks.cancel(id)
+   }
+   pub fn task_events(&self) -> &[TaskEvent] {
+      self.tasks.events()
+   }
+   // bad markup is logged as a warning and shown unstyled
+   pub fn markup(&mut self, src: &str) -> Text<'static> {
+      match markup(src, self.theme) {
+         Ok(text) => text,
+         Err(e) => {
+            self.debug.warn(&e.to_string());
+            Text::raw(src.to_string())
+         }
       }
    }
 }
 
+pub(crate) type Term = Terminal<CrosstermBackend<Box<dyn Write>>>;
+
+// the parts of the tui that are handed to the app as they are
+#[derive(Debug)]
+pub(crate) struct Services {
+   store: Store,
+   anim: Animator,
+   timers: Timers,
+   tasks: Tasks,
+   theme: Theme,
+}
+
 #[derive(Debug)]
 pub struct TUI<A: App> {
    runtime: Runtime,
    debug: Debug,
    cfg: Cfg,
    args: Vec<String>,
+   dirs: AppDirs,
+   services: Services,
+   // the theme and bar colors as init left them, the config theme is applied on top
+   app_theme: Theme,
+   app_bar_colors: BarColors,
+   cfg_themed: bool,
+   capture: Option<Capture>,
    app: A,
 }
 
 impl<A: App> TUI<A> {
    pub fn run() {
-      let mut output = AppOutput::void();
+      Self::run_with(|tui, terminal| tui.run_loop(terminal));
+   }
+
+   // everything around the main loop: config, lua, terminal setup and teardown
+   pub(crate) fn run_with(main: impl FnOnce(&mut TUI<A>, &mut Term)) {
       let cfg_path = match install_cfg::<A>() {
          AppOutput::Ok(cfg_p) => cfg_p,
          AppOutput::Err(e) => {
@@ -81,6 +229,26 @@ impl<A: App> TUI<A> {
          }
       };
 
+      // cli actions work on the config as it is on disk, before any migration
+      let mut args = env::args().skip(1).collect();
+      match CliAction::take(&mut args) {
+         AppOutput::Ok(CliAction::Run) => {}
+         AppOutput::Ok(action) => {
+            action.exec::<A>().out();
+            return;
+         }
+         AppOutput::Err(e) => {
+            AppOutput::<()>::Err(e).out();
+            return;
+         }
+         AppOutput::Nil => return,
+      }
+      if let AppOutput::Err(e) = migrate_cfg::<A>() {
+         AppOutput::<()>::Err(e).out();
+         return;
+      }
+      let color_support = take_color_flag(&mut args);
+
       let cfg_src = match read_cfg::<A>() {
          AppOutput::Ok(s) => s,
          AppOutput::Nil => {
@@ -93,78 +261,162 @@ impl<A: App> TUI<A> {
          }
       };
       let mut cfg = None;
+      let dirs = AppDirs::new::<A>();
+      let (store, store_warnings) = Store::load(dirs.state_dir().map(|d| d.join("store.json")));
+      let mut debug = Debug::new();
+      if let Some(dir) = dirs.state_dir() {
+         debug.log_to(dir.join(format!("{}.log", A::APP_NAME)));
+      }
+      for warning in store_warnings {
+         debug.warn(&warning);
+      }
+      for warning in install_tracing(debug.sink()) {
+         debug.warn(&warning);
+      }
+      let anim = Animator::new();
+      let timers = Timers::new();
+      let api = LuaApi {
+         store: store.clone(),
+         logs: debug.sink(),
+         anim: anim.clone(),
+         timers: timers.clone(),
+      };
 
-      match cfg_path {
-         Some(p) => {
-            let lua = Lua::new();
-            let pkg_dir = format!("{p}/?.lua;{p}/?/?.lua;{p}/?/?/?.lua");
-            let pkg_src = format!("package.path = '{pkg_dir};' .. package.path");
-
-            output = match lua.load(&pkg_src).exec() {
-               Err(e) => app_err!("failed to set lua pkg dir {}", e),
-               _ => AppOutput::<()>::Nil,
-            };
-            match cfg_src {
-               Some(src) => {
-                  output = match lua.load(&src).exec() {
-                     Err(e) => app_err!("failed to load lua {}", e),
-                     _ => {
-                        cfg = Some(lua);
-                        AppOutput::<()>::void()
-                     }
-                  };
+      // a broken lua setup is logged and the app starts without a config
+      if let Some(p) = cfg_path {
+         let lua = Lua::new();
+         let pkg_dir = format!("{p}/?.lua;{p}/?/?.lua;{p}/?/?/?.lua");
+         let pkg_src = format!("package.path = '{pkg_dir};' .. package.path");
+
+         if let Err(e) = api.install(&lua) {
+            debug.error(&format!("failed to install lua api {}", e));
+         } else if let Err(e) = lua.load(&pkg_src).exec() {
+            debug.error(&format!("failed to set lua pkg dir {}", e));
+         } else {
+            match eval_cfg::<A>(&lua, cfg_src) {
+               Err(e) => debug.error(&format!("failed to load lua {}", e)),
+               Ok(warnings) => {
+                  for warning in warnings {
+                     debug.warn(&warning);
+                  }
+                  cfg = Some(lua);
                }
-               None => {}
             }
          }
-         None => {}
       }
 
-      let mut terminal = ratatui::init();
-      output = match TUI::<A>::init(cfg) {
+      // init for raw mode, the alternate screen and the panic hook, the terminal itself
+      // is rebuilt on top of the real stdout once the capture took fd 1
+      let _ = ratatui::init();
+      let mut capture = Capture::start();
+      let writer: Box<dyn Write> = match capture.as_ref().and_then(Capture::tty) {
+         Some(tty) => Box::new(tty),
+         None => Box::new(io::stdout()),
+      };
+      let mut terminal = match Terminal::new(CrosstermBackend::new(writer)) {
+         Ok(t) => t,
+         Err(e) => {
+            drop(capture);
+            ratatui::restore();
+            AppOutput::<()>::Err(format!("failed to create terminal {}", e)).out();
+            return;
+         }
+      };
+
+      let output = match TUI::<A>::init(cfg, args, dirs, store, anim, timers, debug) {
          AppOutput::Ok(mut tui) => {
-            tui.reload_lua();
-            tui.run_loop(&mut terminal);
-            AppOutput::<()>::void()
+            tui.capture = capture.take();
+            if let Some(support) = color_support {
+               tui.runtime.set_color_support(support);
+            }
+            if let AppOutput::Err(e) = tui.refresh_cfg() {
+               tui.debug.error(&e);
+            }
+            main(&mut tui, &mut terminal);
+            capture = tui.capture.take();
+            tui.services.store.save()
          }
          AppOutput::Err(e) => AppOutput::Err(e),
          AppOutput::Nil => AppOutput::<()>::void(),
       };
+      let leftover = capture.as_mut().map(Capture::stop).unwrap_or_default();
+      drop(terminal);
       ratatui::restore();
+      for (stream, line) in leftover {
+         match stream {
+            CaptureStream::Stdout => println!("{line}"),
+            CaptureStream::Stderr => eprintln!("{line}"),
+         }
+      }
       output.out()
    }
 
-   pub(crate) fn init(mut cfg: Cfg) -> AppOutput<TUI<A>> {
+   pub(crate) fn init(
+      mut cfg: Cfg,
+      mut args: Vec<String>,
+      mut dirs: AppDirs,
+      store: Store,
+      anim: Animator,
+      timers: Timers,
+      mut debug: Debug,
+   ) -> AppOutput<TUI<A>> {
       let mut runtime = Runtime::new();
-      let mut debug = Debug::new();
-      let mut args = env::args().skip(1).collect();
-
-      let tui_ref_mut = TUIMutRef {
-         runtime: &mut runtime,
-         debug: &mut debug,
-         cfg: &mut cfg,
-         args: &mut args,
+      let mut services = Services {
+         store,
+         anim,
+         timers,
+         tasks: Tasks::new(4),
+         theme: Theme::default(),
       };
 
+      let tui_ref_mut = TUIMutRef::from(
+         &mut runtime,
+         &mut debug,
+         &mut cfg,
+         &mut args,
+         &mut dirs,
+         &mut services,
+      );
+
       let app = A::init(tui_ref_mut);
       let mut tui = Self {
          runtime,
          app,
+         app_bar_colors: debug.bar.colors,
          debug,
          args,
          cfg,
+         dirs,
+         app_theme: services.theme.clone(),
+         services,
+         cfg_themed: false,
+         capture: None,
       };
+      // the config is evaluated once more right after init, so drop the timers its first
+      // evaluation scheduled and keep only what init itself sets up
+      tui.services.timers.clear_lua();
       tui.lua_fn_call("init");
       tui.debug.current_fn.set_info_msg("init");
       AppOutput::ok(tui)
    }
 
    pub(crate) fn reload_lua(&mut self) -> AppOutput<()> {
+      // timers made by the previous config would otherwise run twice
+      self.services.timers.clear_lua();
+      self.refresh_cfg()
+   }
+
+   // reads and applies the config without touching what lua already scheduled
+   pub(crate) fn refresh_cfg(&mut self) -> AppOutput<()> {
+      self.runtime.set_reload(false);
       let _cfg_dir = match install_cfg::<A>() {
          AppOutput::Ok(p) => p,
          AppOutput::Err(e) => return AppOutput::<()>::Err(e),
          AppOutput::Nil => return AppOutput::<()>::void(),
       };
+      if let AppOutput::Err(e) = migrate_cfg::<A>() {
+         return AppOutput::Err(e);
+      }
 
       let cfg_src = match read_cfg::<A>() {
          AppOutput::Ok(s) => s,
@@ -172,36 +424,83 @@ impl<A: App> TUI<A> {
          AppOutput::Nil => return AppOutput::void(),
       };
 
-      self.runtime.set_reload(false);
       self.runtime.set_just_reloaded(true);
-      self.debug.current_log.set_event_msg("reloaded cfg!");
-      self.load_lua(cfg_src)
+      self.debug.event("reloaded cfg!");
+      let output = self.load_lua(cfg_src);
+      self.apply_cfg();
+      output
+   }
+
+   // pulls the parts of the config katatui itself understands
+   pub(crate) fn apply_cfg(&mut self) {
+      // the config theme goes on top of the one init set up, so removing a key or the whole
+      // theme brings back the app's own colors. an app that never had a config theme keeps
+      // whatever it changed since. the bar takes the theme's colors only while the config
+      // sets a theme and init left the bar's own colors alone
+      let value = cfg_get::<LuaValue>(&self.cfg, "theme").unwrap_or(LuaValue::Nil);
+      if !value.is_nil() || self.cfg_themed {
+         let mut theme = self.app_theme.clone();
+         for warning in theme.apply_cfg(&value) {
+            self.debug.warn(&warning);
+         }
+         self.services.theme = theme;
+         let follow = !value.is_nil() && self.app_bar_colors == BarColors::default();
+         self.debug.bar.colors = match follow {
+            true => self.services.theme.bar_colors(),
+            false => self.app_bar_colors,
+         };
+      }
+      self.cfg_themed = !value.is_nil();
+      if let Some(bar) = cfg_get::<LuaTable>(&self.cfg, "debug_bar") {
+         for warning in self.debug.bar.apply_cfg(&bar) {
+            self.debug.warn(&warning);
+         }
+      }
+      self.sync_lua_theme();
+   }
+
+   // lua reads the theme from the state's app data, only copied over when it changed
+   pub(crate) fn sync_lua_theme(&mut self) {
+      if let Some(lua) = &self.cfg
+         && lua.app_data_ref::<Theme>().as_deref() != Some(&self.services.theme)
+      {
+         lua.set_app_data(self.services.theme.clone());
+      }
    }
 
    pub(crate) fn load_lua(&mut self, src: CfgSrc) -> AppOutput<()> {
       match &self.cfg {
-         Some(lua) => match lua.load(src.unwrap_or("".to_string())).exec() {
+         Some(lua) => match eval_cfg::<A>(lua, src) {
             Err(e) => app_err!("failed to load lua {}", e),
-            _ => AppOutput::<()>::void(),
+            Ok(warnings) => {
+               for warning in warnings {
+                  self.debug.warn(&warning);
+               }
+               AppOutput::<()>::void()
+            }
          },
          None => AppOutput::<()>::void(),
       }
    }
 
+   pub(crate) fn fire_timers(&mut self) {
+      let callbacks = self.services.timers.advance(self.runtime.elapsed(), self.cfg.as_ref());
+      if let Some(lua) = &self.cfg {
+         for (_, f) in callbacks {
+            let name = format!("timer {}", lua_fn_name(&f));
+            let _ = call_lua_func::<()>(lua, &mut self.debug, &name, f, ());
+         }
+      }
+   }
+
    pub(crate) fn lua_fn_call(&mut self, func: &str) -> AppOutput<()> {
-      match &self.cfg {
-         Some(lua) => match lua.globals().get::<mlua::Function>(func) {
-            Ok(f) => {
-               let _ = f.call::<()>(());
-               AppOutput::void()
-            }
-            Err(e) => app_err!("failed to run cfg fn {func} {}", e),
-         },
-         None => AppOutput::<()>::void(),
+      if !CfgFormat::of::<A>().is_lua() {
+         return AppOutput::void();
       }
+      call_lua_fn(&self.cfg, &mut self.debug, func, ())
    }
 
-   pub(crate) fn run_loop(&mut self, terminal: &mut DefaultTerminal) {
+   pub(crate) fn run_loop(&mut self, terminal: &mut Term) {
       let mut last_update = Instant::now();
       let mut last_render = Instant::now();
 
@@ -210,9 +509,11 @@ impl<A: App> TUI<A> {
 
       let mut logic_counter = 0;
       let mut frame_counter = 0;
+      let mut mouse_captured = false;
 
       while self.runtime.is_running() {
          let now = Instant::now();
+         self.sync_mouse_capture(terminal, &mut mouse_captured);
 
          // recompute steps every loop so changes to t_tps / t_fps take effect
          let logic_step = Duration::from_secs_f64(1.0 / self.runtime.t_tps as f64);
@@ -223,7 +524,7 @@ impl<A: App> TUI<A> {
             let tick_start = Instant::now();
             self.logic();
 
-            self.runtime.t_ms = tick_start.elapsed().as_micros();
+            self.runtime.record_tick(tick_start.elapsed().as_micros());
             self.runtime.tick = self.runtime.tick.wrapping_add(1);
 
             last_update += logic_step;
@@ -237,7 +538,7 @@ impl<A: App> TUI<A> {
             self.render_to(terminal);
             self.runtime.frame = self.runtime.frame.wrapping_add(1);
 
-            self.runtime.f_ms = frame_start.elapsed().as_micros();
+            self.runtime.record_frame(frame_start.elapsed().as_micros());
             last_render += render_step;
             frame_counter += 1;
          }
@@ -256,6 +557,20 @@ impl<A: App> TUI<A> {
             last_fps_check = Instant::now();
          }
       }
+      if mouse_captured {
+         let _ = execute!(terminal.backend_mut(), DisableMouseCapture);
+      }
+   }
+
+   // grabbed while the app asks for it or the console is open, the console scrolls with it
+   pub(crate) fn sync_mouse_capture(&self, terminal: &mut Term, captured: &mut bool) {
+      if self.runtime.is_mouse_captured() != *captured {
+         *captured = self.runtime.is_mouse_captured();
+         let _ = match *captured {
+            true => execute!(terminal.backend_mut(), EnableMouseCapture),
+            false => execute!(terminal.backend_mut(), DisableMouseCapture),
+         };
+      }
    }
 
    pub(crate) fn logic(&mut self) {
@@ -265,144 +580,351 @@ impl<A: App> TUI<A> {
             return;
          }
       };
-      self.lua_fn_call("tick");
-      self.debug.current_log.set_info_msg("");
-      self.debug.current_fn.set_info_msg("tick");
-      if eve {
-         if let Ok(e) = event::read() {
-            let tui_mut = TUIMutRef::from(
-               &mut self.runtime,
-               &mut self.debug,
-               &mut self.cfg,
-               &mut self.args,
-            );
-            self.app.logic(tui_mut, Some(e));
+      self.begin_tick();
+      let event = match eve {
+         true => event::read().ok().and_then(|e| self.console_event(e)),
+         false => None,
+      };
+      let tui_mut = TUIMutRef::from(
+         &mut self.runtime,
+         &mut self.debug,
+         &mut self.cfg,
+         &mut self.args,
+         &mut self.dirs,
+         &mut self.services,
+      );
+      self.app.logic(tui_mut, event);
+      self.end_tick();
+   }
+
+   // everything katatui does on a tick before the app's logic runs
+   pub(crate) fn begin_tick(&mut self) {
+      self.debug.stamp(self.runtime.tick(), self.runtime.frame());
+      self.services.anim.advance(self.runtime.elapsed());
+      self.fire_timers();
+      self.services.tasks.drain();
+      for event in self.services.tasks.events() {
+         if let TaskEvent::Failed { id, error } = event {
+            self.debug.warn(&format!("task {id} failed: {error}"));
+         }
+      }
+      if let Some(capture) = &self.capture {
+         for (stream, line) in capture.drain() {
+            self.debug.push(&line, stream.level(), LogSource::Rust);
          }
-      } else {
-         let tui_mut = TUIMutRef::from(
-            &mut self.runtime,
-            &mut self.debug,
-            &mut self.cfg,
-            &mut self.args,
-         );
-         self.app.logic(tui_mut, None);
       }
+      self.lua_fn_call("tick");
+      self.debug.flush_sink();
+      self.debug.current_fn.set_info_msg("tick");
+   }
 
+   pub(crate) fn end_tick(&mut self) {
       self.runtime.set_just_reloaded(false);
-      if self.runtime.is_reloading() {
-         self.reload_lua();
+      self.sync_lua_theme();
+      if self.runtime.is_reloading()
+         && let AppOutput::Err(e) = self.reload_lua()
+      {
+         self.debug.error(&e);
+      }
+   }
+
+   // input meant for the open console never reaches the app
+   pub(crate) fn console_event(&mut self, event: Event) -> Option<Event> {
+      if !self.runtime.is_console() {
+         return Some(event);
+      }
+      let input = self.debug.console.handle(&event, &self.cfg);
+      self.debug.flush_sink();
+      match input {
+         ConsoleInput::Ignored => Some(event),
+         ConsoleInput::Handled => None,
+         ConsoleInput::Close => {
+            self.runtime.set_console(false);
+            None
+         }
       }
    }
 
-   pub(crate) fn render_to(&self, terminal: &mut DefaultTerminal) {
-      match terminal.draw(|frame: &mut Frame| {
+   pub(crate) fn render_to(&self, terminal: &mut Term) {
+      self.services.anim.advance(self.runtime.elapsed());
+      let _ = terminal.draw(|frame: &mut Frame| {
          frame.render_widget(&*self, frame.area());
-      }) {
-         Err(_) => {}
-         _ => {}
+      });
+   }
+}
+
+#[cfg(feature = "async")]
+impl<A: AsyncApp> TUI<Async<A>> {
+   // same as `run` but on a current thread tokio runtime, input comes from an EventStream
+   pub fn run_async() {
+      Self::run_with(|tui, terminal| {
+         let rt = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+            Ok(rt) => rt,
+            Err(e) => {
+               tui.debug.error(&format!("failed to start async runtime {e}"));
+               return;
+            }
+         };
+         rt.block_on(tui.run_loop_async(terminal));
+      });
+   }
+
+   pub(crate) async fn run_loop_async(&mut self, terminal: &mut Term) {
+      use futures_util::StreamExt;
+      use tokio::time::{interval, MissedTickBehavior};
+
+      let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+      let spawner = Spawner::new(tx);
+      let mut events = crossterm::event::EventStream::new();
+      let mut pending = std::collections::VecDeque::new();
+      let mut messages = Vec::new();
+
+      let step = |per_sec: u32| {
+         let mut timer = interval(Duration::from_secs_f64(1.0 / per_sec.max(1) as f64));
+         timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+         timer
       };
+      let (mut t_tps, mut t_fps) = (self.runtime.t_tps, self.runtime.t_fps);
+      let mut logic_timer = step(t_tps);
+      let mut render_timer = step(t_fps);
+
+      let mut last_check = Instant::now();
+      let mut logic_counter = 0;
+      let mut frame_counter = 0;
+      let mut mouse_captured = false;
+
+      while self.runtime.is_running() {
+         self.sync_mouse_capture(terminal, &mut mouse_captured);
+
+         // changes to t_tps / t_fps take effect on the next loop
+         if self.runtime.t_tps != t_tps {
+            t_tps = self.runtime.t_tps;
+            logic_timer = step(t_tps);
+         }
+         if self.runtime.t_fps != t_fps {
+            t_fps = self.runtime.t_fps;
+            render_timer = step(t_fps);
+         }
+
+         tokio::select! {
+            Some(Ok(event)) = events.next() => pending.push_back(event),
+            Some(msg) = rx.recv() => messages.push(msg),
+            _ = logic_timer.tick() => {
+               let tick_start = Instant::now();
+               self.begin_tick();
+               let event = pending.pop_front().and_then(|e| self.console_event(e));
+               let ctx = AsyncCtx {
+                  messages: std::mem::take(&mut messages),
+                  spawner: spawner.clone(),
+               };
+               let tui_mut = TUIMutRef::from(
+                  &mut self.runtime,
+                  &mut self.debug,
+                  &mut self.cfg,
+                  &mut self.args,
+                  &mut self.dirs,
+                  &mut self.services,
+               );
+               // a slow tick runs to the end, the logic timer skips the ticks it missed
+               self.app.app.logic_async(tui_mut, event, ctx).await;
+               let took = tick_start.elapsed();
+               let budget = Duration::from_secs_f64(1.0 / t_tps.max(1) as f64);
+               if took > budget {
+                  self.debug.warn(&format!(
+                     "logic_async took {took:?} of a {budget:?} tick, spawn slow work with \
+                      ctx.spawner"
+                  ));
+               }
+               self.end_tick();
+
+               self.runtime.record_tick(tick_start.elapsed().as_micros());
+               self.runtime.tick = self.runtime.tick.wrapping_add(1);
+               logic_counter += 1;
+            }
+            _ = render_timer.tick() => {
+               let frame_start = Instant::now();
+               self.render_to(terminal);
+               self.runtime.frame = self.runtime.frame.wrapping_add(1);
+               self.runtime.record_frame(frame_start.elapsed().as_micros());
+               frame_counter += 1;
+            }
+         }
+
+         if last_check.elapsed() >= Duration::from_secs(1) {
+            self.runtime.tps = logic_counter as f32;
+            self.runtime.fps = frame_counter as f32;
+            logic_counter = 0;
+            frame_counter = 0;
+            last_check = Instant::now();
+         }
+      }
+      if mouse_captured {
+         let _ = execute!(terminal.backend_mut(), DisableMouseCapture);
+      }
    }
 }
 
 impl<A: App> Widget for &TUI<A> {
    fn render(self, area: Rect, buf: &mut Buffer) {
       self.app.render(
-         TUIRef::from(&self.runtime, &self.debug, &self.cfg, &self.args),
+         TUIRef::from(
+            &self.runtime,
+            &self.debug,
+            &self.cfg,
+            &self.args,
+            &self.dirs,
+            &self.services,
+         ),
          buf,
       );
 
-      if !self.runtime.is_debug() {
-         return;
-      }
-      let dbg_line = Rect::new(0, area.height - 1, area.width, 1);
-      let t_fps = self.runtime.target_fps();
-      let t_tps = self.runtime.target_tps();
-      let fps_digits = t_fps.to_string().len();
-      let tps_digits = t_tps.to_string().len();
-      let frame_mod = self.runtime.frame() % t_fps;
-      let tick_mod = self.runtime.tick() % t_tps;
-
-      let info_txt = format!(
-         " frame: {:0width_fps$} [{}/{}] tick: {:0width_tps$} [{}/{}] ",
-         frame_mod,
-         self.runtime.fps() as u16,
-         t_fps,
-         tick_mod,
-         self.runtime.tps() as u16,
-         t_tps,
-         width_fps = fps_digits,
-         width_tps = tps_digits,
-      );
-      let app_txt = format!(" {}: ", A::APP_NAME);
-      let cfg_txt = match A::CONFIG_FILE {
-         Some(c) => format!(" {c} -> "),
-         None => " no cfg ".to_string(),
+      let (bar_area, rest) = match self.runtime.is_debug() {
+         true => self.debug.bar.split(area),
+         false => (Rect::default(), area),
       };
+      if self.runtime.is_perf() {
+         render_perf(&self.runtime, &self.debug, rest, buf);
+      }
+      if self.runtime.is_console() {
+         self.debug.console.render(&self.debug, rest, buf);
+      }
+      if self.runtime.is_debug() {
+         self.debug.bar.render(
+            &self.runtime,
+            &self.debug,
+            A::APP_NAME,
+            A::CONFIG_FILE,
+            bar_area,
+            buf,
+         );
+      }
+      self.runtime.color_support().downsample(buf);
+   }
+}
 
-      let fn_total_width: usize = 8;
-      let fn_name_str = self.debug.current_fn.msg();
-
-      let mut fn_total_pad = 0;
-      let mut fn_left_pad = 0;
-      let mut fn_right_pad = 0;
-
-      let fn_txt = match A::CONFIG_FILE {
-         Some(f) => {
-            fn_total_pad = fn_total_width.saturating_sub(fn_name_str.len());
-            fn_left_pad = fn_total_pad / 2;
-            fn_right_pad = fn_total_pad - fn_left_pad + 2;
-            format!(
-               " fn {}{}{}()",
-               " ".repeat(fn_left_pad),
-               fn_name_str,
-               " ".repeat(fn_right_pad)
-            )
-         }
-         None => "".to_string(),
-      };
+// the pieces a TUIMutRef points at, for testing adapters without a terminal
+#[cfg(test)]
+pub(crate) struct TestTui {
+   pub(crate) runtime: Runtime,
+   pub(crate) debug: Debug,
+   pub(crate) cfg: Cfg,
+   pub(crate) args: Vec<String>,
+   pub(crate) dirs: AppDirs,
+   services: Services,
+}
+
+#[cfg(test)]
+impl TestTui {
+   pub(crate) fn new() -> Self {
+      Self {
+         runtime: Runtime::new(),
+         debug: Debug::new(),
+         cfg: None,
+         args: Vec::new(),
+         dirs: AppDirs::default(),
+         services: Services {
+            store: Store::default(),
+            anim: Animator::new(),
+            timers: Timers::new(),
+            tasks: Tasks::new(1),
+            theme: Theme::default(),
+         },
+      }
+   }
+   pub(crate) fn tui(&mut self) -> TUIMutRef<'_> {
+      TUIMutRef::from(
+         &mut self.runtime,
+         &mut self.debug,
+         &mut self.cfg,
+         &mut self.args,
+         &mut self.dirs,
+         &mut self.services,
+      )
+   }
+   pub(crate) fn tui_ref(&self) -> TUIRef<'_> {
+      TUIRef::from(
+         &self.runtime,
+         &self.debug,
+         &self.cfg,
+         &self.args,
+         &self.dirs,
+         &self.services,
+      )
+   }
+   pub(crate) fn timers(&self) -> &Timers {
+      &self.services.timers
+   }
+}
 
-      let pad = (dbg_line.width as usize)
-         .saturating_sub(info_txt.width() + app_txt.width() + cfg_txt.width() + fn_txt.width());
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::BarPosition;
+
+   // with `BAR` init changes the bar colors itself
+   struct Themed<const BAR: bool>;
+
+   impl<const BAR: bool> App for Themed<BAR> {
+      const APP_NAME: &'static str = "tui_test";
+      const CONFIG_FILE: Option<&'static str> = Some("init.lua");
+      const DEFAULT_CONFIG_SRC: &'static str = "return {}";
+      fn init(tui: TUIMutRef) -> Self {
+         if BAR {
+            tui.debug.bar.colors.base = Color::Green;
+         }
+         Themed
+      }
+      fn logic(&mut self, _tui: TUIMutRef, _event: Option<Event>) {}
+      fn render(&self, _tui: TUIRef, _buf: &mut Buffer) {}
+   }
 
-      let log_total_width: usize = pad;
-      let log_name_str = self.debug.current_log.msg();
+   fn themed<A: App>() -> TUI<A> {
+      let lua = Lua::new();
+      eval_cfg::<A>(&lua, None).unwrap();
+      let (store, anim, timers) = (Store::default(), Animator::new(), Timers::new());
+      let dirs = AppDirs::default();
+      match TUI::<A>::init(Some(lua), Vec::new(), dirs, store, anim, timers, Debug::new()) {
+         AppOutput::Ok(tui) => tui,
+         _ => panic!("init failed"),
+      }
+   }
+   fn set_cfg<A: App>(tui: &mut TUI<A>, src: &str) {
+      let _ = tui.load_lua(Some(src.to_string()));
+      tui.apply_cfg();
+   }
 
-      let log_title = " log: ";
-      let log_total_pad = log_total_width.saturating_sub(log_name_str.len() + log_title.len());
-      let log_left_pad = log_total_pad / 2;
-      let log_right_pad = log_total_pad - log_left_pad;
+   #[test]
+   fn the_bar_keeps_its_own_colors_without_a_config_theme() {
+      let mut tui = themed::<Themed<false>>();
+      tui.apply_cfg();
+      assert_eq!(tui.debug.bar.colors, BarColors::default());
+      assert_eq!(tui.services.theme, Theme::default());
+   }
 
-      let log_txt = format!(
-         "{log_title}{}{}{}",
-         " ".repeat(log_left_pad),
-         log_name_str,
-         " ".repeat(log_right_pad)
-      );
+   #[test]
+   fn a_config_theme_colors_the_bar_until_removed() {
+      let mut tui = themed::<Themed<false>>();
+      set_cfg(&mut tui, "return { theme = 'nord' }");
+      let nord = Theme::preset("nord").unwrap();
+      assert_eq!(tui.services.theme.name(), "nord");
+      assert_eq!(tui.debug.bar.colors, nord.bar_colors());
+      set_cfg(&mut tui, "return {}");
+      assert_eq!(tui.services.theme, Theme::default());
+      assert_eq!(tui.debug.bar.colors, BarColors::default());
+   }
 
-      let dbg_style = Style::default()
-         .bg(Color::LightMagenta)
-         .fg(Color::Black)
-         .add_modifier(Modifier::BOLD);
-      let app_style = dbg_style
-         .clone()
-         .bg(Color::Magenta)
-         .add_modifier(Modifier::BOLD);
-      let cfg_style = dbg_style.clone().bg(Color::White);
-      let fn_style = dbg_style.clone().bg(Color::Magenta);
-      let log_style = dbg_style
-         .clone()
-         .bg(self.debug.current_log.msg_type().color());
-
-      let dbg_text = Line::from(vec![
-         Span::styled(app_txt, app_style),
-         Span::styled(info_txt, cfg_style),
-         Span::styled(log_txt, log_style),
-         Span::styled(cfg_txt, cfg_style),
-         Span::styled(fn_txt, fn_style),
-      ]);
-
-      Paragraph::new(dbg_text)
-         .block(Block::default())
-         .render(dbg_line, buf);
+   #[test]
+   fn bar_colors_set_by_the_app_or_config_win() {
+      let mut tui = themed::<Themed<true>>();
+      set_cfg(&mut tui, "return { theme = 'nord' }");
+      assert_eq!(tui.debug.bar.colors.base, Color::Green);
+      assert_eq!(tui.services.theme.name(), "nord");
+
+      let mut tui = themed::<Themed<false>>();
+      let bar = "debug_bar = { position = 'top', colors = { base = 'red' } }";
+      let src = format!("return {{ theme = 'nord', {bar} }}");
+      set_cfg(&mut tui, &src);
+      assert_eq!(tui.debug.bar.colors.base, Color::Red);
+      assert_eq!(tui.debug.bar.position, BarPosition::Top);
    }
 }